@@ -0,0 +1,132 @@
+use super::eval::Score;
+use super::pawns::{file_mask, pawns_of, ranks_ahead, relative_rank};
+use crate::data::{BoardConfig, BoardPiece, Color, Square};
+use crate::generator::tables::{NOT_A_FILE, NOT_H_FILE};
+use crate::generator::MoveGenerator;
+use strum::IntoEnumIterator;
+
+// Bonus per square a piece can safely move to, and the number of squares
+// that is worth nothing. Indexed by piece kind, pawns and kings have none.
+#[rustfmt::skip]
+const MOBILITY: [(Score, i32); 6] = [
+    (Score::new(0, 0), 0),
+    (Score::new(4, 4), 4),
+    (Score::new(5, 5), 6),
+    (Score::new(2, 4), 6),
+    (Score::new(1, 2), 12),
+    (Score::new(0, 0), 0),
+];
+
+// Weight of a piece attacking the squares around the enemy king
+const ATTACK_WEIGHT: [i32; 6] = [0, 20, 20, 40, 80, 0];
+// Percentage of the attack weight that counts, by the number of attackers.
+// A lone attacker is rarely dangerous.
+const ATTACKER_SCALE: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+
+// Pawns in front of the king on its own and the adjacent files
+const SHIELD_ADVANCED: Score = Score::new(-10, 0);
+const SHIELD_MISSING: Score = Score::new(-25, 0);
+const KING_SEMI_OPEN_FILE: Score = Score::new(-15, 0);
+const KING_OPEN_FILE: Score = Score::new(-25, 0);
+
+/// What the pieces of one side attack, collected in a single pass.
+#[derive(Default, Copy, Clone, Debug)]
+pub struct SideAttacks {
+    pub mobility: Score,
+    /// Pieces attacking the zone around the enemy king.
+    pub king_attackers: usize,
+    pub king_attack_weight: i32,
+}
+
+fn king_sq(config: &BoardConfig, side: Color) -> Option<Square> {
+    match side {
+        Color::White => config.bitboards[BoardPiece::WhiteKing as usize].peek(),
+        Color::Black => config.bitboards[BoardPiece::BlackKing as usize].peek(),
+    }
+}
+
+/// Squares attacked by the pawns of `side`.
+pub fn pawn_attacks(config: &BoardConfig, side: Color) -> u64 {
+    let pawns = pawns_of(config, side);
+    match side {
+        Color::White => ((pawns << 7) & NOT_H_FILE) | ((pawns << 9) & NOT_A_FILE),
+        Color::Black => ((pawns >> 7) & NOT_A_FILE) | ((pawns >> 9) & NOT_H_FILE),
+    }
+}
+
+/// Mobility of the knights, bishops, rooks and queens of `side` and their
+/// attacks on the enemy king zone. Squares occupied by friendly pieces or
+/// attacked by enemy pawns do not count as mobility.
+pub fn side_attacks(config: &BoardConfig, gen: &MoveGenerator, side: Color) -> SideAttacks {
+    let own = match side {
+        Color::White => config.white_occupancy(),
+        Color::Black => config.black_occupancy(),
+    };
+    let blockers = config.all_occupancy();
+    let area = !*own & !pawn_attacks(config, !side);
+    let king_zone = match king_sq(config, !side) {
+        Some(sq) => *gen.get_king_atk(sq) | 1 << sq as usize,
+        None => 0,
+    };
+
+    let mut attacks = SideAttacks::default();
+    for p in BoardPiece::iter().filter(|p| p.get_color() == side) {
+        let kind = p as usize % 6;
+        let (bonus, base) = MOBILITY[kind];
+        if ATTACK_WEIGHT[kind] == 0 {
+            continue;
+        }
+        let mut bb = config.bitboards[p as usize];
+        while let Some(sq) = bb.pop_sq() {
+            let atk = *gen.get_piece_atk(p, sq, blockers);
+            let count = (atk & area).count_ones() as i32 - base;
+            attacks.mobility += Score::new(bonus.mg * count, bonus.eg * count);
+            if atk & king_zone != 0 {
+                attacks.king_attackers += 1;
+                attacks.king_attack_weight += ATTACK_WEIGHT[kind];
+            }
+        }
+    }
+    attacks
+}
+
+/// Safety of the king of `side`: its pawn shield, open files next to it and
+/// the `enemy` pieces bearing down on it.
+pub fn king_safety(config: &BoardConfig, side: Color, enemy: &SideAttacks) -> Score {
+    let sq = match king_sq(config, side) {
+        Some(sq) => sq,
+        None => return Score::default(),
+    };
+    let (file, rank): (usize, usize) = sq.into();
+    let own_pawns = pawns_of(config, side);
+    let all_pawns = own_pawns | pawns_of(config, !side);
+    let ahead = ranks_ahead(side, rank);
+
+    let mut score = Score::default();
+    for f in file.saturating_sub(1)..=usize::min(file + 1, 7) {
+        let shield = own_pawns & file_mask(f) & ahead;
+        if shield == 0 {
+            score += SHIELD_MISSING;
+        } else {
+            // The pawn closest to the king shields it
+            let closest = match side {
+                Color::White => shield.trailing_zeros(),
+                Color::Black => 63 - shield.leading_zeros(),
+            };
+            let pawn_rank = closest as usize / 8;
+            if relative_rank(side, pawn_rank) > relative_rank(side, rank) + 1 {
+                score += SHIELD_ADVANCED;
+            }
+        }
+
+        if all_pawns & file_mask(f) == 0 {
+            score += KING_OPEN_FILE;
+        } else if own_pawns & file_mask(f) == 0 {
+            score += KING_SEMI_OPEN_FILE;
+        }
+    }
+
+    let scale = ATTACKER_SCALE[enemy.king_attackers.min(ATTACKER_SCALE.len() - 1)];
+    score -= Score::new(enemy.king_attack_weight * scale / 100, 0);
+    score
+}
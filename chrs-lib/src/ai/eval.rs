@@ -1,140 +1,412 @@
+use super::attacks::{king_safety, side_attacks};
+use super::pawns::{evaluate_pawns, unstoppable_passers, PawnEntry, PawnTable};
 use crate::data::{BoardConfig, BoardPiece, Color, Move, Square};
+use crate::generator::MoveGenerator;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
 use strum::IntoEnumIterator;
 
-const MATERIAL_SCORE: [i32; 12] = [
-    100, 300, 350, 500, 1000, 10000, -100, -300, -350, -500, -1000, -10000,
+/// A middlegame and an endgame value that are blended by the game phase.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Score {
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Self { mg, eg }
+    }
+
+    /// Blend the values for `phase`, which goes from `MAX_PHASE` in the
+    /// opening down to 0 when only kings and pawns are left.
+    pub fn taper(self, phase: i32) -> i32 {
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for Score {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.mg + rhs.mg, self.eg + rhs.eg)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Score {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.mg - rhs.mg, self.eg - rhs.eg)
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl Neg for Score {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.mg, -self.eg)
+    }
+}
+
+pub const MAX_PHASE: i32 = 24;
+
+// Indexed by piece kind: pawn, knight, bishop, rook, queen, king
+const PHASE_INC: [i32; 6] = [0, 1, 1, 2, 4, 0];
+
+const MATERIAL: [Score; 6] = [
+    Score::new(82, 94),
+    Score::new(337, 281),
+    Score::new(365, 297),
+    Score::new(477, 512),
+    Score::new(1025, 936),
+    Score::new(0, 0),
+];
+
+// Piece-square tables of PeSTO from White's point of view, rank 1 first like
+// the square indices.
+
+#[rustfmt::skip]
+const MG_PAWN: [i32; 64] =
+[
+       0,    0,    0,    0,    0,    0,    0,    0,
+     -35,   -1,  -20,  -23,  -15,   24,   38,  -22,
+     -26,   -4,   -4,  -10,    3,    3,   33,  -12,
+     -27,   -2,   -5,   12,   17,    6,   10,  -25,
+     -14,   13,    6,   21,   23,   12,   17,  -23,
+      -6,    7,   26,   31,   65,   56,   25,  -20,
+      98,  134,   61,   95,   68,  126,   34,  -11,
+       0,    0,    0,    0,    0,    0,    0,    0
+];
+
+#[rustfmt::skip]
+const EG_PAWN: [i32; 64] =
+[
+       0,    0,    0,    0,    0,    0,    0,    0,
+      13,    8,    8,   10,   13,    0,    2,   -7,
+       4,    7,   -6,    1,    0,   -5,   -1,   -8,
+      13,    9,   -3,   -7,   -7,   -8,    3,   -1,
+      32,   24,   13,    5,   -2,    4,   17,   17,
+      94,  100,   85,   67,   56,   53,   82,   84,
+     178,  173,  158,  134,  147,  132,  165,  187,
+       0,    0,    0,    0,    0,    0,    0,    0
 ];
 
 #[rustfmt::skip]
-const PAWN_SCORE: [i32; 64] =
+const MG_KNIGHT: [i32; 64] =
 [
-     0,   0,   0,   0,   0,   0,   0,   0,
-     0,   0,   0, -10, -10,   0,   0,   0,
-     0,   0,   0,   5,   5,   0,   0,   0,
-     5,   5,  10,  20,  20,   5,   5,   5,
-    10,  10,  10,  20,  20,  10,  10,  10,
-    20,  20,  20,  30,  30,  30,  20,  20,
-    30,  30,  30,  40,  40,  30,  30,  30,
-    90,  90,  90,  90,  90,  90,  90,  90
+    -105,  -21,  -58,  -33,  -17,  -28,  -19,  -23,
+     -29,  -53,  -12,   -3,   -1,   18,  -14,  -19,
+     -23,   -9,   12,   10,   19,   17,   25,  -16,
+     -13,    4,   16,   13,   28,   19,   21,   -8,
+      -9,   17,   19,   53,   37,   69,   18,   22,
+     -47,   60,   37,   65,   84,  129,   73,   44,
+     -73,  -41,   72,   36,   23,   62,    7,  -17,
+    -167,  -89,  -34,  -49,   61,  -97,  -15, -107
 ];
 
-// knight positional score
 #[rustfmt::skip]
-const KNIGHT_SCORE: [i32; 64] =
+const EG_KNIGHT: [i32; 64] =
 [
-    -5, -10,   0,   0,   0,   0, -10,  -5,
-    -5,   0,   0,   0,   0,   0,   0,  -5,
-    -5,   5,  20,  10,  10,  20,   5,  -5,
-    -5,  10,  20,  30,  30,  20,  10,  -5,
-    -5,  10,  20,  30,  30,  20,  10,  -5,
-    -5,   5,  20,  20,  20,  20,   5,  -5,
-    -5,   0,   0,  10,  10,   0,   0,  -5,
-    -5,   0,   0,   0,   0,   0,   0,  -5
- ];
-
-// bishop positional score
+     -29,  -51,  -23,  -15,  -22,  -18,  -50,  -64,
+     -42,  -20,  -10,   -5,   -2,  -20,  -23,  -44,
+     -23,   -3,   -1,   15,   10,   -3,  -20,  -22,
+     -18,   -6,   16,   25,   16,   17,    4,  -18,
+     -17,    3,   22,   22,   22,   11,    8,  -18,
+     -24,  -20,   10,    9,   -1,   -9,  -19,  -41,
+     -25,   -8,  -25,   -2,   -9,  -25,  -24,  -52,
+     -58,  -38,  -13,  -28,  -31,  -27,  -63,  -99
+];
+
 #[rustfmt::skip]
-const BISHOP_SCORE: [i32; 64] =
+const MG_BISHOP: [i32; 64] =
 [
-     0,   0, -10,   0,   0, -10,   0,   0,
-     0,  30,   0,   0,   0,   0,  30,   0,
-     0,  10,   0,   0,   0,   0,  10,   0,
-     0,   0,  10,  20,  20,  10,   0,   0,
-     0,   0,  10,  20,  20,  10,   0,   0,
-     0,   0,   0,  10,  10,   0,   0,   0,
-     0,   0,   0,   0,   0,   0,   0,   0,
-     0,   0,   0,   0,   0,   0,   0,   0
+     -33,   -3,  -14,  -21,  -13,  -12,  -39,  -21,
+       4,   15,   16,    0,    7,   21,   33,    1,
+       0,   15,   15,   15,   14,   27,   18,   10,
+      -6,   13,   13,   26,   34,   12,   10,    4,
+      -4,    5,   19,   50,   37,   37,    7,   -2,
+     -16,   37,   43,   40,   35,   50,   37,   -2,
+     -26,   16,  -18,  -13,   30,   59,   18,  -47,
+     -29,    4,  -82,  -37,  -25,  -42,    7,   -8
 ];
 
-// rook positional score
 #[rustfmt::skip]
-const ROOK_SCORE: [i32; 64] =
+const EG_BISHOP: [i32; 64] =
 [
-     0,   0,   0,  20,  20,   0,   0,   0,
-     0,   0,  10,  20,  20,  10,   0,   0,
-     0,   0,  10,  20,  20,  10,   0,   0,
-     0,   0,  10,  20,  20,  10,   0,   0,
-     0,   0,  10,  20,  20,  10,   0,   0,
-     0,   0,  10,  20,  20,  10,   0,   0,
-    50,  50,  50,  50,  50,  50,  50,  50,
-    50,  50,  50,  50,  50,  50,  50,  50
+     -23,   -9,  -23,   -5,   -9,  -16,   -5,  -17,
+     -14,  -18,   -7,   -1,    4,   -9,  -15,  -27,
+     -12,   -3,    8,   10,   13,    3,   -7,  -15,
+      -6,    3,   13,   19,    7,   10,   -3,   -9,
+      -3,    9,   12,    9,   14,   10,    3,    2,
+       2,   -8,    0,   -1,   -2,    6,    0,    4,
+      -8,   -4,    7,  -12,   -3,  -13,   -4,  -14,
+     -14,  -21,  -11,   -8,   -7,   -9,  -17,  -24
+];
 
+#[rustfmt::skip]
+const MG_ROOK: [i32; 64] =
+[
+     -19,  -13,    1,   17,   16,    7,  -37,  -26,
+     -44,  -16,  -20,   -9,   -1,   11,   -6,  -71,
+     -45,  -25,  -16,  -17,    3,    0,   -5,  -33,
+     -36,  -26,  -12,   -1,    9,   -7,    6,  -23,
+     -24,  -11,    7,   26,   24,   35,   -8,  -20,
+      -5,   19,   26,   36,   17,   45,   61,   16,
+      27,   32,   58,   62,   80,   67,   26,   44,
+      32,   42,   32,   51,   63,    9,   31,   43
 ];
 
-// king positional score
 #[rustfmt::skip]
-const KING_SCORE: [i32; 64] =
+const EG_ROOK: [i32; 64] =
 [
-     0,   0,   5,   0, -15,   0,  10,   0,
-     0,   5,   5,  -5,  -5,   0,   5,   0,
-     0,   0,   5,  10,  10,   5,   0,   0,
-     0,   5,  10,  20,  20,  10,   5,   0,
-     0,   5,  10,  20,  20,  10,   5,   0,
-     0,   5,   5,  10,  10,   5,   5,   0,
-     0,   0,   5,   5,   5,   5,   0,   0,
-     0,   0,   0,   0,   0,   0,   0,   0
-];
-
-const fn get_pawn_score(sq: Square, c: Color) -> i32 {
-    match c {
-        Color::White => PAWN_SCORE[sq as usize],
-        Color::Black => PAWN_SCORE[sq.mirror() as usize],
-    }
+      -9,    2,    3,   -1,   -5,  -13,    4,  -20,
+      -6,   -6,    0,    2,   -9,   -9,  -11,   -3,
+      -4,    0,   -5,   -1,   -7,  -12,   -8,  -16,
+       3,    5,    8,    4,   -5,   -6,   -8,  -11,
+       4,    3,   13,    1,    2,    1,   -1,    2,
+       7,    7,    7,    5,    4,   -3,   -5,   -3,
+      11,   13,   13,   11,   -3,    3,    8,    3,
+      13,   10,   18,   15,   12,   12,    8,    5
+];
+
+#[rustfmt::skip]
+const MG_QUEEN: [i32; 64] =
+[
+      -1,  -18,   -9,   10,  -15,  -25,  -31,  -50,
+     -35,   -8,   11,    2,    8,   15,   -3,    1,
+     -14,    2,  -11,   -2,   -5,    2,   14,    5,
+      -9,  -26,   -9,  -10,   -2,   -4,    3,   -3,
+     -27,  -27,  -16,  -16,   -1,   17,   -2,    1,
+     -13,  -17,    7,    8,   29,   56,   47,   57,
+     -24,  -39,   -5,    1,  -16,   57,   28,   54,
+     -28,    0,   29,   12,   59,   44,   43,   45
+];
+
+#[rustfmt::skip]
+const EG_QUEEN: [i32; 64] =
+[
+     -33,  -28,  -22,  -43,   -5,  -32,  -20,  -41,
+     -22,  -23,  -30,  -16,  -16,  -23,  -36,  -32,
+     -16,  -27,   15,    6,    9,   17,   10,    5,
+     -18,   28,   19,   47,   31,   34,   39,   23,
+       3,   22,   24,   45,   57,   40,   57,   36,
+     -20,    6,    9,   49,   47,   35,   19,    9,
+     -17,   20,   32,   41,   58,   25,   30,    0,
+      -9,   22,   22,   27,   27,   19,   10,   20
+];
+
+#[rustfmt::skip]
+const MG_KING: [i32; 64] =
+[
+     -15,   36,   12,  -54,    8,  -28,   24,   14,
+       1,    7,   -8,  -64,  -43,  -16,    9,    8,
+     -14,  -14,  -22,  -46,  -44,  -30,  -15,  -27,
+     -49,   -1,  -27,  -39,  -46,  -44,  -33,  -51,
+     -17,  -20,  -12,  -27,  -30,  -25,  -14,  -36,
+      -9,   24,    2,  -16,  -20,    6,   22,  -22,
+      29,   -1,  -20,   -7,   -8,   -4,  -38,  -29,
+     -65,   23,   16,  -15,  -56,  -34,    2,   13
+];
+
+#[rustfmt::skip]
+const EG_KING: [i32; 64] =
+[
+     -53,  -34,  -21,  -11,  -28,  -14,  -24,  -43,
+     -27,  -11,    4,   13,   14,    4,   -5,  -17,
+     -19,   -3,   11,   21,   23,   16,    7,   -9,
+     -18,   -4,   21,   24,   27,   23,    9,  -11,
+      -8,   22,   24,   27,   26,   33,   26,    3,
+      10,   17,   23,   15,   20,   45,   44,   13,
+     -12,   17,   14,   17,   17,   38,   23,   11,
+     -74,  -35,  -18,  -18,  -11,   15,    4,  -17
+];
+
+const MG_TABLES: [&[i32; 64]; 6] = [
+    &MG_PAWN, &MG_KNIGHT, &MG_BISHOP, &MG_ROOK, &MG_QUEEN, &MG_KING,
+];
+const EG_TABLES: [&[i32; 64]; 6] = [
+    &EG_PAWN, &EG_KNIGHT, &EG_BISHOP, &EG_ROOK, &EG_QUEEN, &EG_KING,
+];
+
+/// Middlegame material value of `p`, kings are worth nothing.
+pub fn piece_value(p: BoardPiece) -> i32 {
+    MATERIAL[p as usize % 6].mg
 }
 
-const fn get_knight_score(sq: Square, c: Color) -> i32 {
-    match c {
-        Color::White => KNIGHT_SCORE[sq as usize],
-        Color::Black => KNIGHT_SCORE[sq.mirror() as usize],
-    }
+/// Piece-square value of `p` on `sq` for its own side, without material.
+pub fn pst_score(p: BoardPiece, sq: Square) -> Score {
+    let kind = p as usize % 6;
+    let sq = match p.get_color() {
+        Color::White => sq as usize,
+        Color::Black => sq.mirror() as usize,
+    };
+    Score::new(MG_TABLES[kind][sq], EG_TABLES[kind][sq])
 }
 
-const fn get_bishop_score(sq: Square, c: Color) -> i32 {
-    match c {
-        Color::White => BISHOP_SCORE[sq as usize],
-        Color::Black => BISHOP_SCORE[sq.mirror() as usize],
-    }
+/// Game phase computed from the non-pawn material left on the board.
+pub fn game_phase(config: &BoardConfig) -> i32 {
+    let phase: i32 = BoardPiece::iter()
+        .map(|p| PHASE_INC[p as usize % 6] * config.bitboards[p as usize].count_ones() as i32)
+        .sum();
+    // Early promotions can take it past the starting position
+    phase.min(MAX_PHASE)
 }
 
-const fn get_rook_score(sq: Square, c: Color) -> i32 {
-    match c {
-        Color::White => ROOK_SCORE[sq as usize],
-        Color::Black => ROOK_SCORE[sq.mirror() as usize],
+/// Bonus for the side to move.
+const TEMPO: Score = Score::new(10, 5);
+
+const PIECE_NAMES: [&str; 6] = ["Pawn", "Knight", "Bishop", "Rook", "Queen", "King"];
+
+/// Breakdown of [`evaluate`] into its terms. Every term is indexed by side
+/// and scored from that side's point of view.
+#[derive(Default, Copy, Clone, Debug)]
+pub struct EvalTrace {
+    pub material: [Score; 2],
+    /// Piece-square bonus of each piece kind, pawns to king.
+    pub psqt: [[Score; 2]; 6],
+    pub pawns: [Score; 2],
+    pub mobility: [Score; 2],
+    pub king_safety: [Score; 2],
+    pub tempo: [Score; 2],
+    pub phase: i32,
+}
+
+impl EvalTrace {
+    /// Every term with its name, in display order.
+    pub fn terms(&self) -> Vec<(String, [Score; 2])> {
+        let mut terms = vec![("Material".to_string(), self.material)];
+        for (name, psqt) in PIECE_NAMES.iter().zip(self.psqt) {
+            terms.push((format!("{} PST", name), psqt));
+        }
+        terms.push(("Pawn Structure".to_string(), self.pawns));
+        terms.push(("Mobility".to_string(), self.mobility));
+        terms.push(("King Safety".to_string(), self.king_safety));
+        terms.push(("Tempo".to_string(), self.tempo));
+        terms
+    }
+
+    /// Sum of all the terms of `side`.
+    pub fn side_total(&self, side: Color) -> Score {
+        let side = side as usize;
+        let mut total = self.material[side]
+            + self.pawns[side]
+            + self.mobility[side]
+            + self.king_safety[side]
+            + self.tempo[side];
+        for psqt in self.psqt {
+            total += psqt[side];
+        }
+        total
+    }
+
+    /// The tapered evaluation from White's point of view.
+    pub fn score(&self) -> i32 {
+        (self.side_total(Color::White) - self.side_total(Color::Black)).taper(self.phase)
     }
 }
 
-const fn get_king_score(sq: Square, c: Color) -> i32 {
-    match c {
-        Color::White => KING_SCORE[sq as usize],
-        Color::Black => KING_SCORE[sq.mirror() as usize],
+impl std::fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pawns = |v: i32| v as f32 / 100.0;
+        writeln!(
+            f,
+            "{:>15} | {:>13} | {:>13} | {:>6}",
+            "Term", "White", "Black", "Total"
+        )?;
+        writeln!(
+            f,
+            "{:>15} | {:>6} {:>6} | {:>6} {:>6} | {:>6}",
+            "", "MG", "EG", "MG", "EG", ""
+        )?;
+        writeln!(f, "{:-<16}+{:-<15}+{:-<15}+{:-<7}", "", "", "", "")?;
+        let mut rows = self.terms();
+        rows.push((
+            "Total".to_string(),
+            [self.side_total(Color::White), self.side_total(Color::Black)],
+        ));
+        for (name, [white, black]) in rows {
+            if name == "Total" {
+                writeln!(f, "{:-<16}+{:-<15}+{:-<15}+{:-<7}", "", "", "", "")?;
+            }
+            writeln!(
+                f,
+                "{:>15} | {:>6.2} {:>6.2} | {:>6.2} {:>6.2} | {:>+6.2}",
+                name,
+                pawns(white.mg),
+                pawns(white.eg),
+                pawns(black.mg),
+                pawns(black.eg),
+                pawns((white - black).taper(self.phase)),
+            )?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Phase: {}/{}", self.phase, MAX_PHASE)?;
+        write!(f, "Evaluation: {:+.2} (White side)", pawns(self.score()))
     }
 }
 
-pub fn evaluate(config: &BoardConfig) -> i32 {
-    let mut score = 0;
-    use BoardPiece::*;
+fn trace(config: &BoardConfig, gen: &MoveGenerator, pawn_entry: &PawnEntry) -> EvalTrace {
+    let mut trace = EvalTrace {
+        phase: game_phase(config),
+        ..Default::default()
+    };
     for p in BoardPiece::iter() {
+        let side = p.get_color() as usize;
+        let kind = p as usize % 6;
         let mut bb = config.bitboards[p as usize];
-        while *bb > 0 {
-            let pos = bb.pop_sq().unwrap();
-            let mat_score = MATERIAL_SCORE[p as usize];
-            let pos_score = match p {
-                WhiteKing | BlackKing => get_king_score(pos, p.get_color()),
-                WhitePawn | BlackPawn => get_pawn_score(pos, p.get_color()),
-                WhiteRook | BlackRook => get_rook_score(pos, p.get_color()),
-                // WhiteQueen | BlackQueen => get_queen_score(pos, p.get_color()),
-                WhiteQueen | BlackQueen => 0,
-                WhiteBishop | BlackBishop => get_bishop_score(pos, p.get_color()),
-                WhiteKnight | BlackKnight => get_knight_score(pos, p.get_color()),
-            };
-
-            score += match p.get_color() {
-                Color::White => mat_score + pos_score,
-                Color::Black => mat_score - pos_score,
-            }
+        while let Some(sq) = bb.pop_sq() {
+            trace.material[side] += MATERIAL[kind];
+            trace.psqt[kind][side] += pst_score(p, sq);
         }
     }
-    score
+
+    let unstoppable = unstoppable_passers(config, pawn_entry);
+    let white = side_attacks(config, gen, Color::White);
+    let black = side_attacks(config, gen, Color::Black);
+    for side in [Color::White, Color::Black] {
+        let (own, enemy) = match side {
+            Color::White => (&white, &black),
+            Color::Black => (&black, &white),
+        };
+        let i = side as usize;
+        trace.pawns[i] = pawn_entry.score[i] + unstoppable[i];
+        trace.mobility[i] = own.mobility;
+        trace.king_safety[i] = king_safety(config, side, enemy);
+    }
+    trace.tempo[config.get_active_color() as usize] = TEMPO;
+    trace
+}
+
+/// Evaluation of `config` from White's point of view.
+pub fn evaluate(config: &BoardConfig, gen: &MoveGenerator, pawns: &mut PawnTable) -> i32 {
+    trace(config, gen, &pawns.probe(config)).score()
+}
+
+/// Evaluate `config` keeping every term apart, to find out what the
+/// evaluation is made of.
+pub fn evaluate_traced(config: &BoardConfig, gen: &MoveGenerator) -> EvalTrace {
+    trace(config, gen, &evaluate_pawns(config))
+}
+
+/// Same as [`evaluate`] but from the point of view of the side to move, as
+/// required by the negamax framework.
+pub fn evaluate_relative(config: &BoardConfig, gen: &MoveGenerator, pawns: &mut PawnTable) -> i32 {
+    match config.get_active_color() {
+        Color::White => evaluate(config, gen, pawns),
+        Color::Black => -evaluate(config, gen, pawns),
+    }
 }
 
 #[rustfmt::skip]
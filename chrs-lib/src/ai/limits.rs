@@ -0,0 +1,117 @@
+use crate::data::Color;
+use std::time::Duration;
+
+/// When a search has to give up. Without any limit the search runs to the
+/// AI's configured depth. Time and node limits lift the depth limit unless
+/// `depth` is given as well.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SearchLimits {
+    pub depth: Option<usize>,
+    /// Exact time to spend on the move.
+    pub movetime: Option<Duration>,
+    pub wtime: Option<Duration>,
+    pub btime: Option<Duration>,
+    pub winc: Option<Duration>,
+    pub binc: Option<Duration>,
+    /// Moves left until the next time control, sudden death if missing.
+    pub moves_to_go: Option<u32>,
+    pub nodes: Option<usize>,
+    /// Only stop through the stop handle.
+    pub infinite: bool,
+}
+
+/// Deadlines of a search, relative to its start. No new iteration is
+/// started once the soft deadline is near, the running one is aborted at
+/// the hard deadline.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Deadlines {
+    pub soft: Option<Duration>,
+    pub hard: Option<Duration>,
+}
+
+impl SearchLimits {
+    // Time kept in reserve for the communication with a GUI.
+    const OVERHEAD: Duration = Duration::from_millis(30);
+    // Number of moves we plan for in sudden death games.
+    const DEFAULT_MOVES_TO_GO: u32 = 30;
+    // How far past the soft deadline a single iteration may run.
+    const HARD_FACTOR: u32 = 4;
+
+    pub fn depth(depth: usize) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    pub fn movetime(movetime: Duration) -> Self {
+        Self {
+            movetime: Some(movetime),
+            ..Default::default()
+        }
+    }
+
+    pub fn nodes(nodes: usize) -> Self {
+        Self {
+            nodes: Some(nodes),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the limits stop a search with `side` to move before any depth
+    /// is reached. Only the clock of `side` counts, the other one does not
+    /// limit this search.
+    pub fn is_bounded(&self, side: Color) -> bool {
+        let clock = match side {
+            Color::White => self.wtime,
+            Color::Black => self.btime,
+        };
+        self.infinite || self.movetime.is_some() || clock.is_some() || self.nodes.is_some()
+    }
+
+    /// Time allocation for `side` to move.
+    pub fn deadlines(&self, side: Color) -> Deadlines {
+        if self.infinite {
+            return Deadlines::default();
+        }
+        if let Some(movetime) = self.movetime {
+            let t = movetime
+                .saturating_sub(Self::OVERHEAD)
+                .max(Duration::from_millis(1));
+            // All of it is meant to be used, iterations are only cut short
+            // by the hard deadline.
+            return Deadlines {
+                soft: None,
+                hard: Some(t),
+            };
+        }
+
+        let (time, inc) = match side {
+            Color::White => (self.wtime, self.winc),
+            Color::Black => (self.btime, self.binc),
+        };
+        let time = match time {
+            Some(time) => time,
+            None => return Deadlines::default(),
+        };
+        let inc = inc.unwrap_or_default();
+        let available = time
+            .saturating_sub(Self::OVERHEAD)
+            .max(Duration::from_millis(1));
+
+        let moves_to_go = self.moves_to_go.unwrap_or(Self::DEFAULT_MOVES_TO_GO).max(1);
+        let soft = (available / moves_to_go + inc * 3 / 4).min(available);
+        // Never use more than what is left, nor most of it on a single move
+        // unless the time control ends with this move.
+        let cap = if moves_to_go == 1 {
+            available
+        } else {
+            available / 2
+        };
+        let hard = (soft * Self::HARD_FACTOR).min(cap).max(soft);
+        Deadlines {
+            soft: Some(soft),
+            hard: Some(hard),
+        }
+    }
+}
@@ -1,20 +1,132 @@
+mod attacks;
 mod eval;
+mod limits;
 mod negamax;
+mod pawns;
 mod transposition;
 
 use crate::data::{BoardConfig, Move};
 use crate::generator::MoveGenerator;
-pub use negamax::NegaMaxAI;
+pub use eval::{evaluate_traced, EvalTrace, Score, MAX_PHASE};
+pub use limits::{Deadlines, SearchLimits};
+pub use negamax::{InfoCallback, NegaMaxAI};
 use std::time::Duration;
+pub use transposition::{DEFAULT_TT_SIZE_MB, MAX_TT_SIZE_MB};
+
+/// Scores at or beyond this in either direction are mates. A mate is worth
+/// 49000 less the plies to it and no line is longer than 64 plies.
+pub const MATE_BOUND: i32 = 49000 - 64;
 
 pub trait AI {
     fn get_best_move(&mut self, config: &BoardConfig, gen: &MoveGenerator) -> Option<Move>;
     fn get_stats(&self) -> AIStat;
+    /// Lines of the last completed iteration of the last search, best first.
+    /// The first one starts with the move returned by `get_best_move`.
+    fn get_lines(&self) -> Vec<PvLine>;
 }
 
 #[derive(Default, Copy, Clone, Debug)]
 pub struct AIStat {
+    /// Nodes visited by the search, quiescence nodes included.
     pub node_count: usize,
+    pub qnode_count: usize,
     pub time: Duration,
     pub max_depth: usize,
+    /// Moves searched again with the full window after beating the null
+    /// window of the principal variation search.
+    pub pvs_researches: usize,
+    /// Iterations searched again after failing outside the aspiration window.
+    pub aspiration_researches: usize,
+    pub null_move_cutoffs: usize,
+    /// Reduced moves searched again to the full depth after beating alpha.
+    pub lmr_researches: usize,
+    pub reverse_futility_cutoffs: usize,
+    pub futility_pruned: usize,
+    pub delta_pruned: usize,
+    /// Captures skipped in the quiescence search for losing material.
+    pub see_pruned: usize,
+}
+
+/// Search techniques that can be turned off, to measure what they gain.
+#[derive(Copy, Clone, Debug)]
+pub struct SearchFeatures {
+    /// Principal variation search, moves after the first are searched with a
+    /// null window.
+    pub pvs: bool,
+    /// Start the iterations with a narrow window around the last score.
+    pub aspiration: bool,
+    /// Null move pruning, cut nodes where passing still beats beta.
+    pub null_move: bool,
+    /// Late move reductions, search late quiet moves less deep.
+    pub lmr: bool,
+    /// Search one ply deeper when in check.
+    pub check_extensions: bool,
+    /// Cut nodes whose static evaluation is far above beta near the horizon.
+    pub reverse_futility: bool,
+    /// Skip quiet moves in nodes far below alpha near the horizon.
+    pub futility: bool,
+    /// Skip captures in the quiescence search that can not reach alpha.
+    pub delta_pruning: bool,
+    /// Skip captures in the quiescence search that lose material according
+    /// to the static exchange evaluation.
+    pub see_pruning: bool,
+    /// Search all check evasions in the quiescence search instead of
+    /// standing pat.
+    pub quiescence_evasions: bool,
+}
+
+impl Default for SearchFeatures {
+    fn default() -> Self {
+        Self {
+            pvs: true,
+            aspiration: true,
+            null_move: true,
+            lmr: true,
+            check_extensions: true,
+            reverse_futility: true,
+            futility: true,
+            delta_pruning: true,
+            see_pruning: true,
+            quiescence_evasions: true,
+        }
+    }
+}
+
+/// Progress report emitted after every completed iteration of the search.
+#[derive(Default, Clone, Debug)]
+pub struct SearchInfo {
+    pub depth: usize,
+    /// Score in centipawns from the point of view of the side to move.
+    pub score: i32,
+    pub nodes: usize,
+    pub time: Duration,
+    pub pv: Vec<Move>,
+    /// Every line of the iteration best first, the first one is `score` and
+    /// `pv`. There are more with [`NegaMaxAI::multi_pv`].
+    pub lines: Vec<PvLine>,
+    /// Permille of the transposition table in use.
+    pub hashfull: usize,
+}
+
+impl SearchInfo {
+    /// Number of moves until mate if the score is a mate score. Negative
+    /// values mean the side to move is getting mated.
+    pub fn mate_in(&self) -> Option<i32> {
+        NegaMaxAI::mate_in(self.score)
+    }
+}
+
+/// A line found by the search and its score in centipawns from the point of
+/// view of the side to move.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct PvLine {
+    pub score: i32,
+    pub pv: Vec<Move>,
+}
+
+impl PvLine {
+    /// Number of moves until mate if the score is a mate score.
+    pub fn mate_in(&self) -> Option<i32> {
+        NegaMaxAI::mate_in(self.score)
+    }
 }
@@ -1,23 +1,53 @@
 use super::eval::*;
-use super::transposition::{SearchFlag, TT};
-use super::{AIStat, AI};
+use super::pawns::PawnTable;
+use super::transposition::{SearchFlag, TTEntry, TTMove, TT};
+use super::{AIStat, PvLine, SearchFeatures, SearchInfo, SearchLimits, AI, MATE_BOUND};
 use crate::{
-    data::{BoardConfig, Move},
+    data::{BoardConfig, BoardPiece, Color, Move},
     generator::MoveGenerator,
 };
 use instant::Instant;
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use strum::IntoEnumIterator;
+
+pub type InfoCallback = Box<dyn FnMut(&SearchInfo) + Send>;
 
 pub struct NegaMaxAI {
     pub depth: usize,
     pub quiescence_depth: usize,
+    /// Score a draw costs the side the AI is playing for, in centipawns.
+    /// Positive values make the AI avoid draws against weaker opposition.
+    pub contempt: i32,
+    /// Limits of the next searches, `depth` is used when they set none.
+    pub limits: SearchLimits,
+    /// Number of best lines searched, each excluding the first moves of the
+    /// ones before. Only the first line is needed to play.
+    pub multi_pv: usize,
+    pub features: SearchFeatures,
     pub stats: AIStat,
     killer_moves: [[Option<Move>; Self::MAX_DEPTH]; 2],
     history_moves: [[i32; 64]; 12],
     table: TT,
+    pawn_table: PawnTable,
     pv_length: [usize; 64],
     pv_table: [[Option<Move>; 64]; 64],
     score_pv: bool,
     follow_pv: bool,
+    // Hashes of the positions leading to the current node, game history first
+    path: Vec<u64>,
+    // Set when a score depends on the path through a repetition
+    path_dependent: bool,
+    // Set for the node reached by a null move
+    after_null: bool,
+    // Root moves of the lines already found in this iteration
+    excluded: Vec<Move>,
+    lines: Vec<PvLine>,
+    stop: Arc<AtomicBool>,
+    stopped: bool,
+    deadline: Option<Instant>,
+    on_info: Option<InfoCallback>,
 }
 
 impl Default for NegaMaxAI {
@@ -25,14 +55,28 @@ impl Default for NegaMaxAI {
         Self {
             depth: 5,
             quiescence_depth: 4,
+            contempt: 0,
+            limits: Default::default(),
+            multi_pv: 1,
+            features: Default::default(),
             stats: Default::default(),
             killer_moves: [[None; Self::MAX_DEPTH]; 2],
             history_moves: [[0; Self::MAX_DEPTH]; 12],
             table: Default::default(),
+            pawn_table: Default::default(),
             pv_length: [0; Self::MAX_DEPTH],
             pv_table: [[None; Self::MAX_DEPTH]; Self::MAX_DEPTH],
             score_pv: false,
             follow_pv: false,
+            path: Vec::new(),
+            after_null: false,
+            excluded: Vec::new(),
+            lines: Vec::new(),
+            path_dependent: false,
+            stop: Default::default(),
+            stopped: false,
+            deadline: None,
+            on_info: None,
         }
     }
 }
@@ -40,8 +84,26 @@ impl Default for NegaMaxAI {
 impl NegaMaxAI {
     const MIN: i32 = -50000;
     const MAX: i32 = 50000;
-    const MATING_SCORE: i32 = -49000;
     const MAX_DEPTH: usize = 64;
+    const MATING_SCORE: i32 = -MATE_BOUND - Self::MAX_DEPTH as i32;
+    // Deepest iteration we allow, leaving room in the ply indexed tables for
+    // the quiescence search.
+    const MAX_ITERATION: usize = Self::MAX_DEPTH / 2;
+    const NULL_MOVE_MIN_DEPTH: usize = 3;
+    // Margin per ply of depth left for reverse futility pruning
+    const REVERSE_FUTILITY_MARGIN: i32 = 120;
+    const REVERSE_FUTILITY_MAX_DEPTH: usize = 3;
+    // Margin by depth left for futility pruning
+    const FUTILITY_MARGIN: [i32; 3] = [0, 200, 350];
+    // Slack for positional gains when delta pruning captures
+    const DELTA_MARGIN: i32 = 200;
+    // Moves searched at full depth before the later ones are reduced
+    const LMR_MIN_MOVES: usize = 3;
+    const LMR_MIN_DEPTH: usize = 3;
+    // Half width of the first aspiration window, in centipawns
+    const ASPIRATION_WINDOW: i32 = 50;
+    // Earlier iterations are too unstable to guess the score from
+    const ASPIRATION_MIN_DEPTH: usize = 4;
 
     pub fn new(depth: usize, qdepth: usize) -> Self {
         let mut ai = Self::default();
@@ -50,25 +112,167 @@ impl NegaMaxAI {
         ai
     }
 
-    fn score_move(&mut self, m: &Move, ply: usize) -> i32 {
+    /// Handle that can be used to abort a running search from another thread.
+    /// The search returns the best move of the last completed iteration.
+    pub fn stop_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// Register a callback that is invoked after every completed iteration of
+    /// the iterative deepening loop.
+    pub fn set_info_callback(&mut self, f: InfoCallback) {
+        self.on_info = Some(f);
+    }
+
+    pub fn clear_info_callback(&mut self) {
+        self.on_info = None;
+    }
+
+    /// Forget everything learned from previous searches.
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    /// Replace the transposition table with an empty one of at most `mb`
+    /// megabytes.
+    pub fn set_hash_size(&mut self, mb: usize) {
+        self.table = TT::new(mb);
+    }
+
+    /// Size of the transposition table in kilobytes, it can be less than
+    /// asked for with [`Self::set_hash_size`].
+    pub fn hash_size_kb(&self) -> usize {
+        self.table.size_kb()
+    }
+
+    pub fn mate_in(score: i32) -> Option<i32> {
+        if score.abs() < MATE_BOUND {
+            return None;
+        }
+        let plies = -Self::MATING_SCORE - score.abs();
+        let moves = (plies + 1) / 2;
+        Some(if score > 0 { moves } else { -moves })
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+        if let Some(nodes) = self.limits.nodes {
+            self.stopped = self.stats.node_count >= nodes;
+        }
+        // Looking at the clock is too slow to do at every node
+        if self.stats.node_count & 2047 == 0 {
+            self.stopped |= self.stop.load(Ordering::Relaxed)
+                || self.deadline.map(|d| Instant::now() >= d).unwrap_or(false);
+        }
+        self.stopped
+    }
+
+    fn max_iteration(&self, side: Color) -> usize {
+        let depth = match self.limits.depth {
+            Some(depth) => depth,
+            None if self.limits.is_bounded(side) => Self::MAX_ITERATION,
+            None => self.depth,
+        };
+        usize::min(depth, Self::MAX_ITERATION)
+    }
+
+    // Hashes of the earlier positions that can still repeat, oldest first.
+    fn game_path(config: &BoardConfig) -> Vec<u64> {
+        let history = &config.move_history;
+        let plies = usize::min(config.get_halfmove_clock() as usize, history.len());
+        history.hashes().skip(history.len() - plies).collect()
+    }
+
+    fn draw_score(&self, ply: usize) -> i32 {
+        // Odd plies are the opponent's moves
+        if ply % 2 == 1 {
+            self.contempt
+        } else {
+            -self.contempt
+        }
+    }
+
+    // Checkmate takes priority over the fifty move rule, the move reaching
+    // the limit may deliver it.
+    fn is_fifty_move_draw(config: &mut BoardConfig, gen: &MoveGenerator) -> bool {
+        let side = config.get_active_color();
+        config.get_halfmove_clock() >= 100
+            && (!config.is_king_in_check(gen, side)
+                || !gen.gen_all_moves(side, config, false).is_empty())
+    }
+
+    // A position counts as drawn as soon as it repeats once, either within
+    // the search or with the game played so far.
+    fn is_repetition(&self, config: &BoardConfig) -> bool {
+        let reversible = config.get_halfmove_clock() as usize;
+        self.path
+            .iter()
+            .rev()
+            .take(reversible)
+            .skip(1)
+            .step_by(2)
+            .any(|h| *h == config.get_hash())
+    }
+
+    fn get_pv(&self) -> Vec<Move> {
+        self.pv_table[0][..self.pv_length[0]]
+            .iter()
+            .map_while(|m| *m)
+            .collect()
+    }
+
+    // Make `pv` the line followed by the next search of the root.
+    fn set_pv(&mut self, pv: &[Move]) {
+        self.pv_table[0] = [None; Self::MAX_DEPTH];
+        for (i, m) in pv.iter().enumerate() {
+            self.pv_table[0][i] = Some(*m);
+        }
+        self.pv_length[0] = pv.len();
+    }
+
+    fn score_move(
+        &mut self,
+        m: &Move,
+        ply: usize,
+        config: &BoardConfig,
+        gen: &MoveGenerator,
+    ) -> i32 {
         if self.score_pv && self.pv_table[0][ply] == Some(*m) {
             self.score_pv = false;
             self.follow_pv = true;
             return 20000;
         }
         if m.capture.is_some() {
-            return score_mvv_lva(m);
-        } else {
-            if self.killer_moves[0][ply] == Some(*m) {
-                return 9000;
-            } else if self.killer_moves[1][ply] == Some(*m) {
-                return 8000;
+            // Captures that win or trade material go before the killers,
+            // those losing material after every quiet move.
+            if gen.see(config, *m) >= 0 {
+                10000 + score_mvv_lva(m)
             } else {
-                return self.history_moves[m.p as usize][m.to as usize];
+                score_mvv_lva(m) - 10000
             }
+        } else if self.killer_moves[0][ply] == Some(*m) {
+            9000
+        } else if self.killer_moves[1][ply] == Some(*m) {
+            8000
+        } else {
+            self.history_moves[m.p as usize][m.to as usize]
         }
     }
 
+    fn is_killer(&self, m: &Move, ply: usize) -> bool {
+        self.killer_moves[0][ply] == Some(*m) || self.killer_moves[1][ply] == Some(*m)
+    }
+
+    // Whether `side` has anything besides its king and pawns.
+    fn has_pieces(config: &BoardConfig, side: Color) -> bool {
+        BoardPiece::iter()
+            .filter(|p| p.get_color() == side && !p.is_pawn())
+            .filter(|p| !matches!(p, BoardPiece::WhiteKing | BoardPiece::BlackKing))
+            .any(|p| config.bitboards[p as usize].non_zero())
+    }
+
     fn nega_max(
         &mut self,
         config: &mut BoardConfig,
@@ -80,40 +284,134 @@ impl NegaMaxAI {
     ) -> i32 {
         self.stats.node_count += 1;
         self.stats.max_depth = usize::max(self.stats.max_depth, depth);
+        if self.should_stop() {
+            return 0;
+        }
+        // Leave room for the child's entries in the ply indexed tables
+        if ply >= Self::MAX_DEPTH - 1 {
+            return evaluate_relative(config, gen, &mut self.pawn_table);
+        }
         self.pv_length[ply] = ply;
 
+        if ply > 0 && (Self::is_fifty_move_draw(config, gen) || self.is_repetition(config)) {
+            self.path_dependent = true;
+            return self.draw_score(ply);
+        }
+
         let alpha_orig = alpha;
-        if let Some(entry) = self.table.get(&config.get_hash()) {
-            if entry.depth >= depth {
-                match entry.flag {
-                    SearchFlag::Exact => {
-                        self.pv_table[ply][ply] = entry.best;
-                        for next_ply in (ply + 1)..self.pv_length[ply + 1] {
-                            self.pv_table[ply][next_ply] = self.pv_table[ply + 1][next_ply];
+        // Never cut at the root, we need a move to play and a full pv.
+        if ply > 0 {
+            if let Some(entry) = self.table.probe(config.get_hash(), ply) {
+                if entry.depth >= depth {
+                    match entry.flag {
+                        SearchFlag::Exact => {
+                            // The rest of the line is not stored in the table,
+                            // the pv is cut short at this move.
+                            self.pv_table[ply][ply] =
+                                entry.best.and_then(|m| m.to_move(config, gen));
+                            self.pv_length[ply] = ply + 1;
+                            return entry.value;
                         }
-                        self.pv_length[ply] = self.pv_length[ply + 1];
-                        return entry.value;
-                    }
-                    SearchFlag::Lowerbound => alpha = i32::max(alpha, entry.value),
-                    SearchFlag::Upperbound => beta = i32::min(beta, entry.value),
-                };
-            }
+                        SearchFlag::Lowerbound => alpha = i32::max(alpha, entry.value),
+                        SearchFlag::Upperbound => beta = i32::min(beta, entry.value),
+                    };
+                }
 
-            if alpha >= beta {
-                return entry.value;
+                if alpha >= beta {
+                    return entry.value;
+                }
             }
         }
 
+        let in_check = config.is_king_in_check(gen, config.get_active_color());
+        // Look one ply further when in check, there are few replies and the
+        // line may be forcing. Checks at the horizon are left to the
+        // quiescence search.
+        let depth = if in_check && self.features.check_extensions && depth > 0 {
+            depth + 1
+        } else {
+            depth
+        };
+
         if depth == 0 {
-            return self.quiescence(config, gen, alpha, beta, self.quiescence_depth, ply + 1);
+            return self.quiescence(config, gen, alpha, beta, self.quiescence_depth, ply);
         }
-        if ply > Self::MAX_DEPTH - 1 {
-            return evaluate(config);
+
+        let after_null = std::mem::take(&mut self.after_null);
+        let static_eval = if in_check || ply == 0 {
+            None
+        } else {
+            Some(evaluate_relative(config, gen, &mut self.pawn_table))
+        };
+
+        // So far above beta this close to the horizon that no move is going
+        // to lose it all (reverse futility pruning).
+        if let Some(eval) = static_eval {
+            let margin = Self::REVERSE_FUTILITY_MARGIN * depth as i32;
+            if self.features.reverse_futility
+                && depth <= Self::REVERSE_FUTILITY_MAX_DEPTH
+                && beta.abs() < MATE_BOUND
+                && eval - margin >= beta
+            {
+                self.stats.reverse_futility_cutoffs += 1;
+                return eval - margin;
+            }
         }
 
-        let in_check = config.is_king_in_check(gen, config.get_active_color());
+        // Give the opponent a free move, if that still does not get the score
+        // below beta a real move surely won't and the node is cut. Not in
+        // check where passing is illegal, not twice in a row and not without
+        // pieces where having to move can be a disadvantage (zugzwang).
+        if self.features.null_move
+            && ply > 0
+            && depth >= Self::NULL_MOVE_MIN_DEPTH
+            && !in_check
+            && !after_null
+            && beta < MATE_BOUND
+            && Self::has_pieces(config, config.get_active_color())
+            && matches!(static_eval, Some(eval) if eval >= beta)
+        {
+            let reduction = if depth > 6 { 3 } else { 2 };
+            let commit = config.make_null_move();
+            self.path.push(commit.hash);
+            self.after_null = true;
+            // The pv is not followed through the null move
+            let follow_pv = std::mem::replace(&mut self.follow_pv, false);
+            let score = -self.nega_max(
+                config,
+                gen,
+                -beta,
+                -beta + 1,
+                depth.saturating_sub(reduction + 1),
+                ply + 1,
+            );
+            self.after_null = false;
+            self.follow_pv = follow_pv;
+            self.path.pop();
+            config.undo_null_move(&commit);
+            if self.stopped {
+                return 0;
+            }
+            if score >= beta {
+                self.stats.null_move_cutoffs += 1;
+                // A mate found after passing is not a real one
+                return beta;
+            }
+        }
+
+        // Too far below alpha this close to the horizon for a quiet move to
+        // make up for it (futility pruning).
+        let futile = self.features.futility
+            && depth < Self::FUTILITY_MARGIN.len()
+            && alpha.abs() < MATE_BOUND
+            && matches!(static_eval, Some(eval) if eval + Self::FUTILITY_MARGIN[depth] <= alpha);
+
         let mut value = Self::MIN;
+        let mut best_move = None;
         let mut moves = gen.gen_all_moves(config.get_active_color(), config, false);
+        if ply == 0 && !self.excluded.is_empty() {
+            moves.retain(|m| !self.excluded.contains(m));
+        }
         if self.follow_pv {
             if moves.iter().any(|m| self.pv_table[0][ply] == Some(*m)) {
                 self.score_pv = true;
@@ -122,15 +420,88 @@ impl NegaMaxAI {
                 self.follow_pv = false;
             }
         }
-        moves.sort_by(|a, b| self.score_move(b, ply).cmp(&self.score_move(a, ply)));
+        moves.sort_by_cached_key(|m| Reverse(self.score_move(m, ply, config, gen)));
 
+        let path_dependent = std::mem::replace(&mut self.path_dependent, false);
+        self.path.push(config.get_hash());
+        let mut searched = 0;
         for m in moves.iter() {
             if let Some(commit) = config.make_move(*m) {
-                value = i32::max(
-                    value,
-                    -self.nega_max(config, gen, -beta, -alpha, depth - 1, ply + 1),
-                );
+                // Quiet moves after the first may be pruned or reduced unless
+                // they check or are killers.
+                let prunable = searched > 0
+                    && !in_check
+                    && m.capture.is_none()
+                    && !m.is_prom()
+                    && !self.is_killer(m, ply)
+                    && !config.is_king_in_check(gen, config.get_active_color());
+
+                if futile && prunable {
+                    config.undo_commit(&commit);
+                    self.stats.futility_pruned += 1;
+                    continue;
+                }
+
+                // Quiet moves ordered late are unlikely to be any good and are
+                // searched less deep.
+                let reduction = if self.features.lmr
+                    && prunable
+                    && searched >= Self::LMR_MIN_MOVES
+                    && depth >= Self::LMR_MIN_DEPTH
+                {
+                    // Moves that never raised alpha anywhere go even shallower
+                    let history = self.history_moves[m.p as usize][m.to as usize];
+                    if history == 0 && searched >= 2 * Self::LMR_MIN_MOVES {
+                        2
+                    } else {
+                        1
+                    }
+                } else {
+                    0
+                };
+
+                let mut score;
+                if searched == 0 {
+                    score = -self.nega_max(config, gen, -beta, -alpha, depth - 1, ply + 1);
+                } else {
+                    // A reduced move that beats alpha is searched again to the
+                    // full depth.
+                    score = alpha + 1;
+                    if reduction > 0 {
+                        score = -self.nega_max(
+                            config,
+                            gen,
+                            -alpha - 1,
+                            -alpha,
+                            depth - 1 - reduction,
+                            ply + 1,
+                        );
+                        if score > alpha && !self.stopped {
+                            self.stats.lmr_researches += 1;
+                        }
+                    }
+                    if score > alpha && self.features.pvs && !self.stopped {
+                        // Later moves only have to be proven worse than the
+                        // best so far, which a null window does more cheaply.
+                        score = -self.nega_max(config, gen, -alpha - 1, -alpha, depth - 1, ply + 1);
+                        if score > alpha && score < beta && !self.stopped {
+                            self.stats.pvs_researches += 1;
+                            score = -self.nega_max(config, gen, -beta, -alpha, depth - 1, ply + 1);
+                        }
+                    } else if score > alpha && !self.stopped {
+                        score = -self.nega_max(config, gen, -beta, -alpha, depth - 1, ply + 1);
+                    }
+                }
+                searched += 1;
                 config.undo_commit(&commit);
+                if self.stopped {
+                    self.path.pop();
+                    return 0;
+                }
+                if score > value {
+                    value = score;
+                    best_move = Some(*m);
+                }
 
                 if value >= beta {
                     if m.capture.is_none() {
@@ -155,33 +526,88 @@ impl NegaMaxAI {
                 }
             }
         }
+        self.path.pop();
+        let tainted = self.path_dependent;
+        self.path_dependent |= path_dependent;
 
         if moves.len() == 0 {
             if in_check {
                 return Self::MATING_SCORE + ply as i32;
             } else {
-                return 0;
+                return self.draw_score(ply);
             }
         }
 
-        let entry = self
-            .table
-            .entry(config.get_hash())
-            .or_insert(Default::default());
-        entry.value = value;
-        if value <= alpha_orig {
-            entry.flag = SearchFlag::Upperbound;
+        // Scores reached through a repetition only hold on this path, keep
+        // them out of the table so other lines are not misjudged. The same
+        // goes for a root searched without some of its moves.
+        if tainted || (ply == 0 && !self.excluded.is_empty()) {
+            return value;
+        }
+
+        let flag = if value <= alpha_orig {
+            SearchFlag::Upperbound
         } else if value >= beta {
-            entry.flag = SearchFlag::Lowerbound;
+            SearchFlag::Lowerbound
         } else {
-            entry.flag = SearchFlag::Exact;
-        }
-        entry.best = self.pv_table[ply][ply];
-        entry.depth = depth;
+            SearchFlag::Exact
+        };
+        // No move raised alpha when failing low, the best one is unknown
+        let best = match flag {
+            SearchFlag::Upperbound => None,
+            _ => best_move.map(|m| TTMove::from(&m)),
+        };
+        let entry = TTEntry {
+            depth,
+            flag,
+            best,
+            value,
+        };
+        self.table.store(config.get_hash(), ply, entry);
 
         value
     }
 
+    /// Search the root starting with a window around `guess`, the score of
+    /// the previous iteration, widening it until the score falls inside.
+    fn aspiration_search(
+        &mut self,
+        config: &mut BoardConfig,
+        gen: &MoveGenerator,
+        depth: usize,
+        guess: Option<i32>,
+    ) -> i32 {
+        let mut delta = Self::ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = match guess {
+            // Mate scores jump around too much to guess
+            Some(guess)
+                if self.features.aspiration
+                    && depth >= Self::ASPIRATION_MIN_DEPTH
+                    && Self::mate_in(guess).is_none() =>
+            {
+                (guess - delta, guess + delta)
+            }
+            _ => (Self::MIN, Self::MAX),
+        };
+
+        loop {
+            self.follow_pv = true;
+            let score = self.nega_max(config, gen, alpha, beta, depth, 0);
+            if self.stopped {
+                return score;
+            }
+            if score <= alpha && alpha > Self::MIN {
+                alpha = i32::max(score - delta, Self::MIN);
+            } else if score >= beta && beta < Self::MAX {
+                beta = i32::min(score + delta, Self::MAX);
+            } else {
+                return score;
+            }
+            self.stats.aspiration_researches += 1;
+            delta *= 2;
+        }
+    }
+
     fn quiescence(
         &mut self,
         config: &mut BoardConfig,
@@ -192,25 +618,59 @@ impl NegaMaxAI {
         ply: usize,
     ) -> i32 {
         self.stats.node_count += 1;
+        self.stats.qnode_count += 1;
         self.stats.max_depth = usize::max(self.stats.max_depth, depth);
+        if self.should_stop() {
+            return 0;
+        }
 
-        let eval = evaluate(config);
-        if depth == 0 {
+        let eval = evaluate_relative(config, gen, &mut self.pawn_table);
+        if depth == 0 || ply > Self::MAX_DEPTH - 1 {
             return eval;
         }
-        if eval >= beta {
-            return beta;
+
+        // In check standing pat is no option, every evasion is searched.
+        let in_check = self.features.quiescence_evasions
+            && config.is_king_in_check(gen, config.get_active_color());
+        if !in_check {
+            if eval >= beta {
+                return beta;
+            }
+            // Not even winning a queen would get back to alpha
+            if self.features.delta_pruning
+                && eval + piece_value(BoardPiece::WhiteQueen) + Self::DELTA_MARGIN < alpha
+            {
+                self.stats.delta_pruned += 1;
+                return alpha;
+            }
+            alpha = i32::max(alpha, eval);
         }
-        alpha = i32::max(alpha, eval);
 
-        let mut moves = gen.gen_all_moves(config.get_active_color(), config, true);
-        moves.sort_by(|a, b| self.score_move(b, ply).cmp(&self.score_move(a, ply)));
+        let mut moves = gen.gen_all_moves(config.get_active_color(), config, !in_check);
+        if in_check && moves.is_empty() {
+            return Self::MATING_SCORE + ply as i32;
+        }
+        moves.sort_by_cached_key(|m| Reverse(self.score_move(m, ply, config, gen)));
 
         for m in moves.iter() {
-            assert!(m.capture.is_some());
+            // The capture can not raise the score to alpha
+            if self.features.delta_pruning && !in_check && !m.is_prom() {
+                let gain = m.capture.map_or(0, piece_value);
+                if eval + gain + Self::DELTA_MARGIN <= alpha {
+                    self.stats.delta_pruned += 1;
+                    continue;
+                }
+            }
+            if self.features.see_pruning && !in_check && gen.see(config, *m) < 0 {
+                self.stats.see_pruned += 1;
+                continue;
+            }
             if let Some(commit) = config.make_move(*m) {
                 let score = -self.quiescence(config, gen, -beta, -alpha, depth - 1, ply + 1);
                 config.undo_commit(&commit);
+                if self.stopped {
+                    return 0;
+                }
                 if score >= beta {
                     return beta;
                 }
@@ -230,22 +690,116 @@ impl AI for NegaMaxAI {
         self.pv_table = [[None; 64]; 64];
         self.score_pv = false;
         self.follow_pv = false;
+        self.stopped = false;
+        self.path = Self::game_path(config);
+        self.path_dependent = false;
+        self.table.new_search();
 
         let mut config = config.clone();
         let now = Instant::now();
+        let deadlines = self.limits.deadlines(config.get_active_color());
+        self.deadline = deadlines.hard.map(|d| now + d);
+        let mut best = None;
 
-        for current_depth in 1..(self.depth + 1) {
-            self.follow_pv = true;
-            self.stats.node_count = 0;
-            self.nega_max(&mut config, gen, Self::MIN, Self::MAX, current_depth, 0);
+        // There can not be more lines than moves
+        let root_moves = gen
+            .gen_all_moves(config.get_active_color(), &mut config, false)
+            .len();
+        let line_count = self.multi_pv.clamp(1, root_moves.max(1));
+        self.lines.clear();
+        for current_depth in 1..(self.max_iteration(config.get_active_color()) + 1) {
+            let mut lines: Vec<PvLine> = Vec::with_capacity(line_count);
+            self.excluded.clear();
+            for index in 0..line_count {
+                // Start from the line of the same rank in the last iteration
+                // unless its move was taken by a better line.
+                let last = self
+                    .lines
+                    .get(index)
+                    .filter(|l| matches!(l.pv.first(), Some(m) if !self.excluded.contains(m)))
+                    .cloned();
+                if line_count > 1 {
+                    self.set_pv(last.as_ref().map_or(&[], |l| &l.pv));
+                }
+                let guess = last.map(|l| l.score);
+                let score = self.aspiration_search(&mut config, gen, current_depth, guess);
+                if self.stopped {
+                    break;
+                }
+                let pv = self.get_pv();
+                match pv.first() {
+                    Some(m) => self.excluded.push(*m),
+                    // The root has no moves, its score is still reported
+                    None if index == 0 => {}
+                    None => break,
+                }
+                lines.push(PvLine { score, pv });
+            }
+            self.excluded.clear();
+            // The search of a later line can turn out better than an earlier
+            // one, the order of equal ones is kept.
+            lines.sort_by_key(|l| Reverse(l.score));
+
+            if self.stopped {
+                // The interrupted iteration can not be trusted, fall back to
+                // the previous one unless we have nothing better.
+                if self.lines.is_empty() {
+                    self.lines = lines;
+                }
+                best = self
+                    .lines
+                    .first()
+                    .and_then(|l| l.pv.first().copied())
+                    .or(self.pv_table[0][0]);
+                break;
+            }
+            self.lines = lines;
+            best = self.lines.first().and_then(|l| l.pv.first().copied());
+
+            if self.on_info.is_some() {
+                let first = self.lines.first().cloned().unwrap_or_default();
+                let info = SearchInfo {
+                    depth: current_depth,
+                    score: first.score,
+                    nodes: self.stats.node_count,
+                    time: now.elapsed(),
+                    pv: first.pv,
+                    lines: self.lines.clone(),
+                    hashfull: self.table.hashfull(),
+                };
+                if let Some(f) = self.on_info.as_mut() {
+                    f(&info);
+                }
+            }
+
+            // The next iteration takes longer than all the previous ones
+            // together, don't start one that can not end before the soft
+            // deadline.
+            if let Some(soft) = deadlines.soft {
+                if now.elapsed() >= soft / 2 {
+                    break;
+                }
+            }
+        }
+
+        // Stopped before the first move was searched, any legal move is
+        // better than none.
+        if best.is_none() {
+            best = gen
+                .gen_all_moves(config.get_active_color(), &mut config, false)
+                .first()
+                .copied();
         }
-        // self.nega_max(&mut config, gen, Self::MIN, Self::MAX, self.depth, 0);
 
         self.stats.time = now.elapsed();
-        self.pv_table[0][0]
+        best
     }
 
     fn get_stats(&self) -> AIStat {
         self.stats
     }
+
+    fn get_lines(&self) -> Vec<PvLine> {
+        self.lines.clone()
+    }
 }
@@ -0,0 +1,252 @@
+use super::eval::Score;
+use crate::data::{BitBoard, BoardConfig, BoardPiece, Color};
+
+const FILE_A: u64 = 0x0101010101010101;
+const RANK_1: u64 = 0xff;
+
+const DOUBLED: Score = Score::new(-10, -25);
+const ISOLATED: Score = Score::new(-5, -15);
+const BACKWARD: Score = Score::new(-8, -10);
+
+// Indexed by the rank of the pawn relative to its side
+#[rustfmt::skip]
+const CONNECTED: [Score; 8] = [
+    Score::new(0, 0), Score::new(3, 0), Score::new(7, 3), Score::new(10, 6),
+    Score::new(18, 12), Score::new(30, 25), Score::new(50, 40), Score::new(0, 0),
+];
+#[rustfmt::skip]
+const PASSED: [Score; 8] = [
+    Score::new(0, 0), Score::new(5, 10), Score::new(10, 15), Score::new(15, 30),
+    Score::new(30, 55), Score::new(50, 95), Score::new(80, 150), Score::new(0, 0),
+];
+// A passed pawn the enemy king can not catch with no pieces left to stop it
+const UNSTOPPABLE: Score = Score::new(0, 400);
+
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+/// Pawn structure of a position, it only depends on the pawns.
+#[derive(Default, Copy, Clone, Debug)]
+pub struct PawnEntry {
+    key: u64,
+    /// Indexed by side, each from its own point of view.
+    pub score: [Score; 2],
+    pub passed: [BitBoard; 2],
+}
+
+/// Cache of pawn structures indexed by [`BoardConfig::get_pawn_hash`]. Pawn
+/// structures change rarely during a search so most lookups hit.
+pub struct PawnTable {
+    entries: Vec<PawnEntry>,
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self {
+            entries: vec![Default::default(); PAWN_TABLE_SIZE],
+        }
+    }
+}
+
+impl PawnTable {
+    pub fn probe(&mut self, config: &BoardConfig) -> PawnEntry {
+        let key = config.get_pawn_hash();
+        let entry = &mut self.entries[key as usize & (PAWN_TABLE_SIZE - 1)];
+        // A position without pawns has key 0 which matches an empty entry,
+        // which is also what it would evaluate to.
+        if entry.key != key {
+            *entry = evaluate_pawns(config);
+            entry.key = key;
+        }
+        *entry
+    }
+}
+
+pub fn file_mask(file: usize) -> u64 {
+    FILE_A << file
+}
+
+// Rank `offset` ranks away from `rank`, empty past the edge of the board.
+// Pawns only stand on a back rank in positions loaded from unvalidated FENs.
+fn rank_mask(rank: usize, offset: isize) -> u64 {
+    match rank.checked_add_signed(offset) {
+        Some(r) if r < 8 => RANK_1 << (8 * r),
+        _ => 0,
+    }
+}
+
+pub fn adjacent_files(file: usize) -> u64 {
+    let mut mask = 0;
+    if file > 0 {
+        mask |= file_mask(file - 1);
+    }
+    if file < 7 {
+        mask |= file_mask(file + 1);
+    }
+    mask
+}
+
+/// Ranks strictly in front of `rank` from the point of view of `side`.
+pub fn ranks_ahead(side: Color, rank: usize) -> u64 {
+    match side {
+        Color::White if rank < 7 => !0 << (8 * (rank + 1)),
+        Color::Black => (1 << (8 * rank)) - 1,
+        _ => 0,
+    }
+}
+
+pub fn relative_rank(side: Color, rank: usize) -> usize {
+    match side {
+        Color::White => rank,
+        Color::Black => 7 - rank,
+    }
+}
+
+pub fn pawns_of(config: &BoardConfig, side: Color) -> u64 {
+    match side {
+        Color::White => *config.bitboards[BoardPiece::WhitePawn as usize],
+        Color::Black => *config.bitboards[BoardPiece::BlackPawn as usize],
+    }
+}
+
+/// Pawn structure terms of `config` for both sides.
+pub fn evaluate_pawns(config: &BoardConfig) -> PawnEntry {
+    let mut entry = PawnEntry::default();
+    for side in [Color::White, Color::Black] {
+        let (score, passed) = side_pawns(config, side);
+        entry.score[side as usize] = score;
+        entry.passed[side as usize] = BitBoard::from(passed);
+    }
+    entry
+}
+
+fn side_pawns(config: &BoardConfig, side: Color) -> (Score, u64) {
+    let us = pawns_of(config, side);
+    let them = pawns_of(config, !side);
+    let (forward, backward): (isize, isize) = match side {
+        Color::White => (1, -1),
+        Color::Black => (-1, 1),
+    };
+
+    let mut score = Score::default();
+    let mut passed = 0;
+    let mut pawns = BitBoard::from(us);
+    while let Some(sq) = pawns.pop_sq() {
+        let (file, rank): (usize, usize) = sq.into();
+        let rel_rank = relative_rank(side, rank);
+        let ahead = ranks_ahead(side, rank);
+        let neighbours = adjacent_files(file);
+
+        // Only the rearmost of doubled pawns is penalized
+        let doubled = us & file_mask(file) & ahead != 0;
+        if doubled {
+            score += DOUBLED;
+        }
+
+        let isolated = us & neighbours == 0;
+        if isolated {
+            score += ISOLATED;
+        }
+
+        let supported = us & neighbours & rank_mask(rank, backward) != 0;
+        let phalanx = us & neighbours & rank_mask(rank, 0) != 0;
+        if supported || phalanx {
+            score += CONNECTED[rel_rank];
+        }
+
+        // Every neighbour has advanced past it and an enemy pawn controls
+        // the square in front of it.
+        if !isolated && !supported && us & neighbours & !ahead == 0 {
+            let stop_attacked =
+                rel_rank < 6 && them & neighbours & rank_mask(rank, 2 * forward) != 0;
+            if stop_attacked {
+                score += BACKWARD;
+            }
+        }
+
+        if !doubled && them & (file_mask(file) | neighbours) & ahead == 0 {
+            score += PASSED[rel_rank];
+            passed |= 1 << sq as usize;
+        }
+    }
+    (score, passed)
+}
+
+/// Bonus for passed pawns that race to promotion faster than the enemy king
+/// when the enemy has no pieces left to stop them. This depends on more than
+/// the pawns, so it is not cached with the pawn structure.
+pub fn unstoppable_passers(config: &BoardConfig, entry: &PawnEntry) -> [Score; 2] {
+    let mut score = [Score::default(); 2];
+    for side in [Color::White, Color::Black] {
+        let enemy_pieces = match !side {
+            Color::White => BoardPiece::WhiteKnight as usize..BoardPiece::WhiteKing as usize,
+            Color::Black => BoardPiece::BlackKnight as usize..BoardPiece::BlackKing as usize,
+        };
+        if enemy_pieces.into_iter().any(|p| *config.bitboards[p] != 0) {
+            continue;
+        }
+        let king = match !side {
+            Color::White => config.bitboards[BoardPiece::WhiteKing as usize].peek(),
+            Color::Black => config.bitboards[BoardPiece::BlackKing as usize].peek(),
+        };
+        let king = match king {
+            Some(king) => king,
+            None => continue,
+        };
+        let (king_file, king_rank): (usize, usize) = king.into();
+        let tempo = (config.get_active_color() != side) as usize;
+
+        let mut passed = entry.passed[side as usize];
+        while let Some(sq) = passed.pop_sq() {
+            let (file, rank): (usize, usize) = sq.into();
+            if *config.all_occupancy() & file_mask(file) & ranks_ahead(side, rank) != 0 {
+                continue;
+            }
+            let rel_rank = relative_rank(side, rank);
+            // The double push saves a move from the starting rank
+            let pawn_dist = (7 - rel_rank).min(5);
+            let promotion_rank = relative_rank(side, 7);
+            let king_dist = king_file
+                .abs_diff(file)
+                .max(king_rank.abs_diff(promotion_rank));
+            if king_dist.saturating_sub(tempo) > pawn_dist {
+                score[side as usize] += UNSTOPPABLE;
+            }
+        }
+    }
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::eval::evaluate_traced;
+    use crate::generator::test_generator;
+
+    #[test]
+    fn pawns_on_back_ranks() {
+        // Only reachable through FENs that were not validated
+        for fen in [
+            "4k3/8/8/8/8/8/8/P3K3 w - - 0 1",
+            "4k2p/8/8/8/8/8/8/4K3 b - - 0 1",
+            "P3k3/8/8/8/8/8/8/4K2p w - - 0 1",
+            "4k2P/8/8/8/8/8/1P6/p3K3 b - - 0 1",
+        ] {
+            let config = BoardConfig::try_from_fen(fen).unwrap();
+            evaluate_pawns(&config);
+            evaluate_traced(&config, test_generator());
+        }
+
+        // A lone pawn with no rank behind it is only isolated
+        let config = BoardConfig::try_from_fen("4k3/8/8/8/8/8/8/P3K3 w - - 0 1").unwrap();
+        let entry = evaluate_pawns(&config);
+        assert_eq!(entry.score[Color::White as usize], ISOLATED);
+
+        // The pawn on a1 supports b2
+        let config = BoardConfig::try_from_fen("4k3/8/8/8/8/8/1P6/P3K3 w - - 0 1").unwrap();
+        let entry = evaluate_pawns(&config);
+        assert_eq!(
+            entry.score[Color::White as usize],
+            CONNECTED[1] + PASSED[0] + PASSED[1]
+        );
+    }
+}
@@ -1,8 +1,11 @@
-use std::collections::HashMap;
+use super::MATE_BOUND;
+use crate::data::{BoardConfig, Move, MoveType, Square};
+use crate::generator::MoveGenerator;
 
-use crate::data::Move;
+pub const DEFAULT_TT_SIZE_MB: usize = 16;
+pub const MAX_TT_SIZE_MB: usize = 1024;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
 pub enum SearchFlag {
     #[default]
     Exact,
@@ -10,12 +13,231 @@ pub enum SearchFlag {
     Upperbound,
 }
 
-#[derive(Debug, Default)]
+/// A move packed into 16 bits, the source and target squares and the kind of
+/// the promotion piece. Only meaningful in the position it was stored for.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct TTMove(u16);
+
+fn prom_kind(m: &Move) -> u16 {
+    match m.move_type {
+        MoveType::Promotion(Some(p)) => p as u16 % 6,
+        _ => 0,
+    }
+}
+
+impl From<&Move> for TTMove {
+    fn from(m: &Move) -> Self {
+        Self(m.from as u16 | (m.to as u16) << 6 | prom_kind(m) << 12)
+    }
+}
+
+impl TTMove {
+    /// Rebuild the full move in `config`. A key collision can hand us the
+    /// move of another position, so it is looked up among the legal moves of
+    /// the piece on the source square and `None` if it is not one of them.
+    pub fn to_move(self, config: &mut BoardConfig, gen: &MoveGenerator) -> Option<Move> {
+        let from = Square::try_from(self.0 as usize & 63).ok()?;
+        let to = Square::try_from((self.0 as usize >> 6) & 63).ok()?;
+        let p = config.get_at_sq(from)?;
+        if p.get_color() != config.get_active_color() {
+            return None;
+        }
+        gen.gen_piece_moves(p, from, config, false)
+            .iter()
+            .find(|m| m.to == to && prom_kind(m) == self.0 >> 12)
+            .copied()
+    }
+}
+
+/// What the search learned about a position.
+#[derive(Debug, Default, Copy, Clone)]
 pub struct TTEntry {
     pub depth: usize,
     pub flag: SearchFlag,
-    pub best: Option<Move>,
+    pub best: Option<TTMove>,
     pub value: i32,
 }
 
-pub type TT = HashMap<u64, TTEntry>;
+// Packed slot of the table, 12 bytes
+#[derive(Default, Copy, Clone)]
+struct Slot {
+    // Upper bits of the key, the lower ones select the bucket
+    check: u32,
+    best: u16,
+    depth: u8,
+    // Generation in the upper 6 bits, the flag in the lower 2. A flag of 0
+    // marks an empty slot.
+    gen_flag: u8,
+    value: i32,
+}
+
+impl Slot {
+    fn is_empty(&self) -> bool {
+        self.gen_flag & 3 == 0
+    }
+
+    fn generation(&self) -> u8 {
+        self.gen_flag >> 2
+    }
+
+    fn flag(&self) -> SearchFlag {
+        match self.gen_flag & 3 {
+            1 => SearchFlag::Exact,
+            2 => SearchFlag::Lowerbound,
+            _ => SearchFlag::Upperbound,
+        }
+    }
+}
+
+/// One slot keeps the deepest search of the current generation, the other
+/// always takes the latest entry.
+#[derive(Default, Copy, Clone)]
+struct Bucket {
+    deep: Slot,
+    recent: Slot,
+}
+
+/// Preallocated transposition table with a power of two number of buckets.
+pub struct TT {
+    buckets: Vec<Bucket>,
+    generation: u8,
+}
+
+impl Default for TT {
+    fn default() -> Self {
+        Self::new(DEFAULT_TT_SIZE_MB)
+    }
+}
+
+impl TT {
+    /// Create a table using at most `mb` megabytes. The number of buckets is
+    /// the largest power of two that fits, so most sizes leave some unused.
+    pub fn new(mb: usize) -> Self {
+        let bytes = mb.clamp(1, MAX_TT_SIZE_MB) * 1024 * 1024;
+        let count = bytes / std::mem::size_of::<Bucket>();
+        // Round down to a power of two so the index is a mask of the key
+        let count = 1 << (usize::BITS - 1 - count.leading_zeros());
+        Self {
+            buckets: vec![Default::default(); count],
+            generation: 0,
+        }
+    }
+
+    /// Size of the table in kilobytes.
+    pub fn size_kb(&self) -> usize {
+        self.buckets.len() * std::mem::size_of::<Bucket>() / 1024
+    }
+
+    pub fn clear(&mut self) {
+        self.buckets.fill(Default::default());
+        self.generation = 0;
+    }
+
+    /// Age the entries, those of earlier searches are replaced first.
+    pub fn new_search(&mut self) {
+        self.generation = (self.generation + 1) & 63;
+    }
+
+    fn index(&self, key: u64) -> usize {
+        key as usize & (self.buckets.len() - 1)
+    }
+
+    fn check(key: u64) -> u32 {
+        (key >> 32) as u32
+    }
+
+    /// Look up `key` for a node `ply` plies from the root.
+    pub fn probe(&self, key: u64, ply: usize) -> Option<TTEntry> {
+        let bucket = &self.buckets[self.index(key)];
+        let check = Self::check(key);
+        [bucket.deep, bucket.recent]
+            .into_iter()
+            .find(|s| !s.is_empty() && s.check == check)
+            .map(|s| TTEntry {
+                depth: s.depth as usize,
+                flag: s.flag(),
+                best: if s.best == 0 {
+                    None
+                } else {
+                    Some(TTMove(s.best))
+                },
+                value: value_from_tt(s.value, ply),
+            })
+    }
+
+    /// Store the result of a search of `key` found `ply` plies from the root.
+    pub fn store(&mut self, key: u64, ply: usize, entry: TTEntry) {
+        let generation = self.generation;
+        let index = self.index(key);
+        let bucket = &mut self.buckets[index];
+        let check = Self::check(key);
+
+        let depth = entry.depth.min(u8::MAX as usize) as u8;
+        let deep = bucket.deep;
+        let slot = if deep.is_empty()
+            || deep.check == check
+            || deep.generation() != generation
+            || depth >= deep.depth
+        {
+            // The entry pushed out gets a second chance in the other slot
+            if !deep.is_empty() && deep.check != check {
+                bucket.recent = deep;
+            }
+            &mut bucket.deep
+        } else {
+            &mut bucket.recent
+        };
+
+        // Keep the move of an earlier search of the same position
+        let mut best = entry.best.map_or(0, |m| m.0);
+        if best == 0 && !slot.is_empty() && slot.check == check {
+            best = slot.best;
+        }
+
+        let flag = match entry.flag {
+            SearchFlag::Exact => 1,
+            SearchFlag::Lowerbound => 2,
+            SearchFlag::Upperbound => 3,
+        };
+        *slot = Slot {
+            check,
+            best,
+            depth,
+            gen_flag: generation << 2 | flag,
+            value: value_to_tt(entry.value, ply),
+        };
+    }
+
+    /// Permille of the table used by the current search, estimated from the
+    /// first thousand slots.
+    pub fn hashfull(&self) -> usize {
+        self.buckets
+            .iter()
+            .flat_map(|b| [b.deep, b.recent])
+            .take(1000)
+            .filter(|s| !s.is_empty() && s.generation() == self.generation)
+            .count()
+    }
+}
+
+// Mate scores are stored relative to the node they were found at and turned
+// back into distances from the root when read.
+fn value_to_tt(value: i32, ply: usize) -> i32 {
+    if value >= MATE_BOUND {
+        value + ply as i32
+    } else if value <= -MATE_BOUND {
+        value - ply as i32
+    } else {
+        value
+    }
+}
+
+fn value_from_tt(value: i32, ply: usize) -> i32 {
+    if value >= MATE_BOUND {
+        value - ply as i32
+    } else if value <= -MATE_BOUND {
+        value + ply as i32
+    } else {
+        value
+    }
+}
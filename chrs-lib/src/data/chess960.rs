@@ -0,0 +1,80 @@
+use super::BoardConfig;
+
+// Placements of the two knights on the five squares left after the
+// bishops and the queen, in the order of the standard numbering.
+const KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+// Puts `piece` on the `nth` empty square of the rank.
+fn place(rank: &mut [Option<char>; 8], nth: usize, piece: char) {
+    let file = (0..8).filter(|f| rank[*f].is_none()).nth(nth).unwrap();
+    rank[file] = Some(piece);
+}
+
+impl BoardConfig {
+    /// Chess960 start position number `index`, between 0 and 959, using the
+    /// standard numbering in which 518 is the classical setup.
+    pub fn from_chess960_index(index: u16) -> Option<BoardConfig> {
+        if index >= 960 {
+            return None;
+        }
+        let mut rank: [Option<char>; 8] = [None; 8];
+        let mut n = index as usize;
+
+        rank[(n % 4) * 2 + 1] = Some('b');
+        n /= 4;
+        rank[(n % 4) * 2] = Some('b');
+        n /= 4;
+
+        place(&mut rank, n % 6, 'q');
+        n /= 6;
+        let (first, second) = KNIGHTS[n];
+        // The second knight is counted before the first one is placed
+        place(&mut rank, second, 'n');
+        place(&mut rank, first, 'n');
+        for piece in ['r', 'k', 'r'] {
+            place(&mut rank, 0, piece);
+        }
+
+        let black: String = rank.iter().map(|p| p.unwrap()).collect();
+        let fen = format!(
+            "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w KQkq - 0 1",
+            black,
+            black.to_uppercase()
+        );
+        let mut config = BoardConfig::from_fen_str(&fen);
+        config.set_chess960(true);
+        Some(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn back_rank(index: u16) -> String {
+        let fen = BoardConfig::from_chess960_index(index).unwrap().get_fen();
+        fen.split('/').next().unwrap().to_string()
+    }
+
+    #[test]
+    fn standard_numbering() {
+        assert_eq!(back_rank(0), "bbqnnrkr");
+        assert_eq!(back_rank(959), "rkrnnqbb");
+        assert!(BoardConfig::from_chess960_index(960).is_none());
+
+        let classical = BoardConfig::from_chess960_index(518).unwrap();
+        assert!(classical.is_chess960());
+        assert_eq!(classical.get_fen(), BoardConfig::default().get_fen());
+    }
+}
@@ -0,0 +1,167 @@
+use super::piece::BoardPiece;
+use super::BoardConfig;
+
+const DARK_SQUARES: u64 = 0xaa55aa55aa55aa55;
+
+impl BoardConfig {
+    /// How many times the current position has occurred in the game,
+    /// counting the current one. Only positions since the last capture or
+    /// pawn move are considered as earlier ones can not come back.
+    pub fn repetition_count(&self) -> usize {
+        self.move_history
+            .hashes()
+            .rev()
+            .take(self.halfmove_clock as usize)
+            .filter(|h| *h == self.hash)
+            .count()
+            + 1
+    }
+
+    /// Neither side has the material left to deliver mate: lone kings, a
+    /// single minor piece, or only bishops that all stand on one colour.
+    pub fn is_insufficient_material(&self) -> bool {
+        use BoardPiece::*;
+        let heavy = [
+            WhitePawn, BlackPawn, WhiteRook, BlackRook, WhiteQueen, BlackQueen,
+        ];
+        if heavy.iter().any(|p| *self.bitboards[*p as usize] > 0) {
+            return false;
+        }
+
+        let knights = *self.bitboards[WhiteKnight as usize] | *self.bitboards[BlackKnight as usize];
+        let bishops = *self.bitboards[WhiteBishop as usize] | *self.bitboards[BlackBishop as usize];
+        if (knights | bishops).count_ones() <= 1 {
+            return true;
+        }
+        knights == 0 && (bishops & DARK_SQUARES == 0 || bishops & !DARK_SQUARES == 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{BoardConfig, Color, GameState, Move};
+    use crate::generator::test_generator;
+
+    fn state(fen: &str) -> GameState {
+        let mut config = BoardConfig::from_fen_str(fen);
+        test_generator().update_state(&mut config);
+        config.get_state()
+    }
+
+    // Plays the UCI moves and returns the state after each of them.
+    fn play(config: &mut BoardConfig, moves: &[&str]) -> Vec<GameState> {
+        let gen = test_generator();
+        let mut states = Vec::new();
+        for s in moves {
+            let m = Move::from_uci(s, config, gen).unwrap();
+            config.apply_move(m);
+            gen.update_state(config);
+            states.push(config.get_state());
+        }
+        states
+    }
+
+    #[test]
+    fn repetitions() {
+        let mut config = BoardConfig::default();
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+        let states = play(&mut config, &shuffle);
+        assert_eq!(states, [GameState::InPlay; 4]);
+        assert_eq!(config.repetition_count(), 2);
+
+        let states = play(&mut config, &shuffle);
+        assert_eq!(states[..3], [GameState::InPlay; 3]);
+        assert_eq!(states[3], GameState::ThreefoldRepetition);
+        assert_eq!(config.repetition_count(), 3);
+
+        play(&mut config, &shuffle);
+        assert_eq!(config.get_state(), GameState::ThreefoldRepetition);
+        let states = play(&mut config, &shuffle);
+        assert_eq!(states[3], GameState::FivefoldRepetition);
+        assert_eq!(config.repetition_count(), 5);
+        assert!(config.get_state().is_draw());
+
+        // Leaving the repeated position ends the draw claim
+        play(&mut config, &["b1c3"]);
+        assert_eq!(config.get_state(), GameState::InPlay);
+    }
+
+    #[test]
+    fn irreversible_moves_reset_repetitions() {
+        let mut config = BoardConfig::default();
+        play(&mut config, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        play(&mut config, &["e2e3", "e7e6"]);
+        let states = play(&mut config, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert_eq!(states, [GameState::InPlay; 4]);
+        assert_eq!(config.repetition_count(), 2);
+    }
+
+    #[test]
+    fn move_rules() {
+        assert_eq!(state("4k3/8/8/8/8/8/8/4KR2 b - - 99 80"), GameState::InPlay);
+        assert_eq!(
+            state("4k3/8/8/8/8/8/8/4KR2 b - - 100 80"),
+            GameState::FiftyMoveRule
+        );
+        assert_eq!(
+            state("4k3/8/8/8/8/8/8/4KR2 b - - 150 80"),
+            GameState::SeventyFiveMoveRule
+        );
+
+        let mut config = BoardConfig::from_fen_str("4k3/8/8/8/8/8/8/4KR2 w - - 99 80");
+        assert_eq!(play(&mut config, &["f1f2"]), [GameState::FiftyMoveRule]);
+        let mut config = BoardConfig::from_fen_str("4k3/8/8/8/8/8/8/4KR2 w - - 149 80");
+        assert_eq!(
+            play(&mut config, &["f1f2"]),
+            [GameState::SeventyFiveMoveRule]
+        );
+        // A pawn move starts the count again
+        let mut config = BoardConfig::from_fen_str("4k3/8/8/8/8/8/P7/4KR2 w - - 99 80");
+        assert_eq!(play(&mut config, &["a2a3"]), [GameState::InPlay]);
+    }
+
+    #[test]
+    fn mate_before_move_rules() {
+        for clock in [99, 149] {
+            let fen = format!("k7/8/1K6/8/8/8/8/7R w - - {} 80", clock);
+            let mut config = BoardConfig::from_fen_str(&fen);
+            assert_eq!(
+                play(&mut config, &["h1h8"]),
+                [GameState::Mate(Color::Black)]
+            );
+        }
+        assert_eq!(
+            state("k6R/8/1K6/8/8/8/8/8 b - - 100 80"),
+            GameState::Mate(Color::Black)
+        );
+        assert_eq!(
+            state("k7/8/1Q6/8/8/8/8/7K b - - 100 80"),
+            GameState::StaleMate
+        );
+    }
+
+    #[test]
+    fn insufficient_material() {
+        for fen in [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1N2K3 b - - 0 1",
+            // Bishops of both sides all on dark squares
+            "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/1B6/B3K3 w - - 0 1",
+        ] {
+            assert_eq!(state(fen), GameState::InsufficientMaterial, "{}", fen);
+        }
+        for fen in [
+            // Bishops on opposite colours can still mate
+            "2b1k3/8/8/8/8/8/8/2B1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1NB1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/1NN1K3 w - - 0 1",
+            "4k3/8/8/8/8/8/P7/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/3RK3 w - - 0 1",
+        ] {
+            assert_eq!(state(fen), GameState::InPlay, "{}", fen);
+        }
+    }
+}
@@ -1,16 +1,70 @@
+use std::fmt::Display;
 use std::str::FromStr;
 
-use crate::data::{BoardMap, CastleFlags, GameState};
-use crate::zobrist::hash;
+use crate::data::{BoardMap, CastleFlags, CastleType, GameState, PositionError};
+use crate::zobrist::{hash, pawn_hash};
 
 use super::piece::{BoardPiece, Color};
 use super::square::Square;
 use super::BoardConfig;
 
+/// Describes why a FEN string could not be parsed. Ranks are numbered the
+/// way they appear on the board, i.e. the first rank of the FEN is rank 8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FenError {
+    Empty,
+    RankCount(usize),
+    InvalidPiece { rank: u8, c: char },
+    RankLength { rank: u8, files: u32 },
+    InvalidColor(String),
+    InvalidCastle(char),
+    InvalidEnPassant(String),
+    InvalidHalfmove(String),
+    InvalidFullmove(String),
+    ExtraField(String),
+    IllegalPosition(Vec<PositionError>),
+}
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use FenError::*;
+        match self {
+            Empty => write!(f, "FEN string is empty"),
+            RankCount(n) => write!(f, "piece placement has {} ranks, expected 8", n),
+            InvalidPiece { rank, c } => {
+                write!(
+                    f,
+                    "invalid piece '{}' in rank {} of piece placement",
+                    c, rank
+                )
+            }
+            RankLength { rank, files } => {
+                write!(
+                    f,
+                    "rank {} of piece placement has {} files, expected 8",
+                    rank, files
+                )
+            }
+            InvalidColor(s) => write!(f, "invalid active color '{}', expected 'w' or 'b'", s),
+            InvalidCastle(c) => write!(f, "invalid castling availability '{}'", c),
+            InvalidEnPassant(s) => write!(f, "invalid en passant target square '{}'", s),
+            InvalidHalfmove(s) => write!(f, "invalid halfmove clock '{}'", s),
+            InvalidFullmove(s) => write!(f, "invalid fullmove number '{}'", s),
+            ExtraField(s) => write!(f, "unexpected extra field '{}'", s),
+            IllegalPosition(errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "illegal position: {}", errors.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
 pub struct Fen;
 
 impl Fen {
-    pub fn make_config_from_str(s: &str) -> BoardConfig {
+    pub fn make_config_from_str(s: &str) -> Result<BoardConfig, FenError> {
         Fen::make_config(s)
     }
 
@@ -40,18 +94,18 @@ impl Fen {
             Color::White => s.push_str(" w "),
             Color::Black => s.push_str(" b "),
         }
-        if c.get_can_white_castle_kingside() {
-            s.push('K');
-        }
-        if c.get_can_white_castle_queenside() {
-            s.push('Q');
-        }
-        if c.get_can_black_castle_kingside() {
-            s.push('k');
+        let mut castle = String::new();
+        for color in [Color::White, Color::Black] {
+            for side in [CastleType::KingSide, CastleType::QueenSide] {
+                if c.can_castle(color, side) {
+                    castle.push(Fen::castle_char(c, color, side));
+                }
+            }
         }
-        if c.get_can_black_castle_queenside() {
-            s.push('q');
+        if castle.is_empty() {
+            castle.push('-');
         }
+        s.push_str(&castle);
         s.push(' ');
         if let Some(pos) = c.get_en_passant_target() {
             s.push_str(&pos.to_string().to_lowercase());
@@ -65,128 +119,174 @@ impl Fen {
         s
     }
 
-    fn get_piece_from_c(c: char) -> BoardPiece {
-        if let Ok(p) = BoardPiece::from_str(&c.to_string()) {
-            p
+    // X-FEN castling: `K` and `Q` unless another rook stands between the
+    // castling rook and the corner, then the file of the rook is used.
+    fn castle_char(c: &BoardConfig, color: Color, side: CastleType) -> char {
+        let rook = c.get_castle_rook(color, side);
+        let (file, rank): (usize, usize) = rook.into();
+        let piece = match color {
+            Color::White => BoardPiece::WhiteRook,
+            Color::Black => BoardPiece::BlackRook,
+        };
+        let outer = match side {
+            CastleType::KingSide => file + 1..8,
+            CastleType::QueenSide => 0..file,
+        };
+        let letter = if c.is_chess960()
+            && outer
+                .into_iter()
+                .any(|f| c.get_at_sq((f, rank).try_into().unwrap()) == Some(piece))
+        {
+            (b'a' + file as u8) as char
+        } else if side == CastleType::KingSide {
+            'k'
         } else {
-            log::error!("Fen Error: {} is invalid piece", c);
-            panic!();
+            'q'
+        };
+        match color {
+            Color::White => letter.to_ascii_uppercase(),
+            Color::Black => letter,
         }
     }
 
-    fn get_sq_from_code(s: &str) -> Square {
-        let a = 'a'.to_ascii_lowercase() as usize;
-
-        let mut it = s.chars();
-        let c = it.next().unwrap();
-
-        let x = if c.is_alphabetic() {
-            c.to_ascii_lowercase() as usize - a
+    // Reads one castling right in any of the `KQkq`, X-FEN or Shredder-FEN
+    // forms. Returns the right, the home square of its rook and whether the
+    // notation is only meaningful in Chess960.
+    fn parse_castle(
+        c: char,
+        bitboards: &BoardMap,
+    ) -> Result<(Color, CastleType, Square, bool), FenError> {
+        let color = if c.is_ascii_uppercase() {
+            Color::White
         } else {
-            log::error!("Fen Error: {} is invalid square", s);
-            panic!();
+            Color::Black
         };
+        let (rank, king, rook) = match color {
+            Color::White => (0, BoardPiece::WhiteKing, BoardPiece::WhiteRook),
+            Color::Black => (7, BoardPiece::BlackKing, BoardPiece::BlackRook),
+        };
+        let sq = |file: usize| Square::try_from((file, rank)).unwrap();
+        let king_file = (0..8).find(|f| bitboards[king as usize].is_set(sq(*f)));
+        let is_rook = |f: &usize| bitboards[rook as usize].is_set(sq(*f));
 
-        let n: String = it.collect();
-        let y = n.parse::<usize>().unwrap() - 1;
-
-        log::debug!("decode {} to {:?}", s, (x, y));
+        match (c.to_ascii_lowercase(), king_file) {
+            // Outermost rook on that side of the king, the standard corner if
+            // there is none so that `validate` can report it.
+            ('k', Some(k)) => {
+                let file = (k + 1..8).rev().find(is_rook).unwrap_or(7);
+                Ok((color, CastleType::KingSide, sq(file), k != 4 || file != 7))
+            }
+            ('q', Some(k)) => {
+                let file = (0..k).find(is_rook).unwrap_or(0);
+                Ok((color, CastleType::QueenSide, sq(file), k != 4 || file != 0))
+            }
+            ('k', None) => Ok((color, CastleType::KingSide, sq(7), false)),
+            ('q', None) => Ok((color, CastleType::QueenSide, sq(0), false)),
+            // The rook can not stand on the file of its own king
+            (f @ 'a'..='h', Some(k)) if f as usize - 'a' as usize != k => {
+                let file = f as usize - 'a' as usize;
+                let side = if file > k {
+                    CastleType::KingSide
+                } else {
+                    CastleType::QueenSide
+                };
+                Ok((color, side, sq(file), true))
+            }
+            _ => Err(FenError::InvalidCastle(c)),
+        }
+    }
 
-        (x, y).try_into().unwrap()
+    fn get_piece_from_c(c: char) -> Option<BoardPiece> {
+        BoardPiece::from_str(&c.to_string()).ok()
     }
 
-    // TODO: Return Result with custom error type
-    fn make_config(fen_str: &str) -> BoardConfig {
+    fn make_config(fen_str: &str) -> Result<BoardConfig, FenError> {
         log::trace!("Making BoardConfig...");
         let mut castle_flags = CastleFlags::default();
+        let mut castle_rooks = [Square::H1, Square::A1, Square::H8, Square::A8];
+        let mut chess960 = false;
         let mut en_passant_target: Option<Square> = None;
         let mut halfmove_clock = 0;
         let mut fullmove_number = 0;
         let mut active_color = Color::White;
         let mut bitboards: BoardMap = Default::default();
 
+        if fen_str.split_whitespace().next().is_none() {
+            return Err(FenError::Empty);
+        }
+
         for (i, data) in fen_str.split_whitespace().enumerate() {
             log::trace!("Parcing Fen field {}, {}", i, data);
             match i {
                 0 => {
-                    for (i, rank) in data.split('/').enumerate() {
+                    let ranks: Vec<&str> = data.split('/').collect();
+                    if ranks.len() != 8 {
+                        return Err(FenError::RankCount(ranks.len()));
+                    }
+                    for (i, rank) in ranks.iter().enumerate() {
+                        let rank_no = 8 - i as u8;
                         let mut x = 0;
                         for c in rank.chars() {
-                            if c.is_digit(10) {
-                                x = x + c.to_digit(10).unwrap();
-                            } else {
-                                log::debug!("Place {c} at {:?}", (7 - i, x));
-                                bitboards[Fen::get_piece_from_c(c) as usize]
-                                    .set(Square::try_from((x as usize, 7 - i)).unwrap());
-                                x = x + 1;
-                            }
-                        }
-                    }
-                }
-                1 => {
-                    if data.len() > 1 {
-                        log::error!("Fen Error: Active color field is wrong");
-                    } else {
-                        if let Some(c) = data.chars().next() {
-                            match c {
-                                'w' => {
-                                    active_color = Color::White;
-                                }
-                                'b' => {
-                                    active_color = Color::Black;
-                                }
+                            match c.to_digit(10) {
+                                Some(n @ 1..=8) => x += n,
                                 _ => {
-                                    log::error!("Fen Error: {} is invalid color", c);
-                                    panic!();
+                                    let p = Fen::get_piece_from_c(c)
+                                        .ok_or(FenError::InvalidPiece { rank: rank_no, c })?;
+                                    if x < 8 {
+                                        log::debug!("Place {c} at {:?}", (7 - i, x));
+                                        bitboards[p as usize]
+                                            .set(Square::try_from((x as usize, 7 - i)).unwrap());
+                                    }
+                                    x += 1;
                                 }
                             }
                         }
+                        if x != 8 {
+                            return Err(FenError::RankLength {
+                                rank: rank_no,
+                                files: x,
+                            });
+                        }
                     }
                 }
+                1 => match data {
+                    "w" => active_color = Color::White,
+                    "b" => active_color = Color::Black,
+                    _ => return Err(FenError::InvalidColor(data.to_string())),
+                },
                 2 => {
-                    if data.len() == 1 && data.chars().next() == Some('-') {
-                    } else {
-                        let mut chars = data.chars();
-                        while let Some(c) = chars.next() {
-                            match c {
-                                'k' => castle_flags.set_black_oo(),
-                                'q' => castle_flags.set_black_ooo(),
-                                'K' => castle_flags.set_white_oo(),
-                                'Q' => castle_flags.set_white_ooo(),
-                                _ => {
-                                    log::error!("Fen Error: {} is invalid", c);
-                                    panic!();
-                                }
-                            }
+                    if data != "-" {
+                        for c in data.chars() {
+                            let (color, side, rook, is_960) = Fen::parse_castle(c, &bitboards)?;
+                            castle_flags.set(color, side);
+                            castle_rooks[CastleFlags::index(color, side)] = rook;
+                            chess960 |= is_960;
                         }
                     }
                 }
                 3 => {
-                    if data.len() == 1 && data.chars().next() == Some('-') {
-                    } else {
-                        en_passant_target = Some(Self::get_sq_from_code(data));
+                    if data != "-" {
+                        match Square::from_str(data) {
+                            Ok(sq) => en_passant_target = Some(sq),
+                            Err(_) => return Err(FenError::InvalidEnPassant(data.to_string())),
+                        }
                     }
                 }
                 4 => {
-                    if let Ok(n) = data.parse::<u8>() {
+                    if let Ok(n) = data.parse::<u16>() {
                         halfmove_clock = n;
                     } else {
-                        log::error!("Fen Error: {} is invalid halfmove", data);
-                        panic!();
+                        return Err(FenError::InvalidHalfmove(data.to_string()));
                     }
                 }
                 5 => {
-                    if let Ok(n) = data.parse::<u8>() {
+                    if let Ok(n) = data.parse::<u16>() {
                         fullmove_number = n;
                     } else {
-                        log::error!("Fen Error: {} is invalid fullmove", data);
-                        panic!();
+                        return Err(FenError::InvalidFullmove(data.to_string()));
                     }
                 }
-                _ => {
-                    log::error!("Fen Error: Extra Fields");
-                    panic!();
-                }
+                _ => return Err(FenError::ExtraField(data.to_string())),
             };
         }
 
@@ -195,14 +295,240 @@ impl Fen {
             active_color,
             en_passant_target,
             castle_flags,
+            castle_rooks,
+            chess960,
             halfmove_clock,
             fullmove_number,
             bitboards,
             move_history: Default::default(),
             state: GameState::InPlay,
             hash: 0,
+            pawn_hash: 0,
         };
         c.hash = hash(&c);
-        c
+        c.pawn_hash = pawn_hash(&c);
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+    fn fen_error(fen: &str) -> FenError {
+        BoardConfig::try_from_fen(fen).err().unwrap()
+    }
+
+    #[test]
+    fn piece_placement_errors() {
+        assert_eq!(fen_error(""), FenError::Empty);
+        assert_eq!(fen_error("  "), FenError::Empty);
+        assert_eq!(
+            fen_error("rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            FenError::RankCount(7)
+        );
+        assert_eq!(
+            fen_error("rnbqkbnr/ppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            FenError::RankLength { rank: 7, files: 7 }
+        );
+        assert_eq!(
+            fen_error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP1/RNBQKBNR w KQkq - 0 1"),
+            FenError::RankLength { rank: 2, files: 9 }
+        );
+        assert_eq!(
+            fen_error("rnbqkbnr/pppppppp/8/8/4X3/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            FenError::InvalidPiece { rank: 4, c: 'X' }
+        );
+        assert_eq!(
+            fen_error("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN0 w KQkq - 0 1"),
+            FenError::InvalidPiece { rank: 1, c: '0' }
+        );
+    }
+
+    #[test]
+    fn field_errors() {
+        let placement = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR";
+        let error = |rest: &str| fen_error(&format!("{} {}", placement, rest));
+        assert_eq!(
+            error("white KQkq - 0 1"),
+            FenError::InvalidColor("white".to_string())
+        );
+        assert_eq!(error("w KQxq - 0 1"), FenError::InvalidCastle('x'));
+        assert_eq!(
+            error("w KQkq e9 0 1"),
+            FenError::InvalidEnPassant("e9".to_string())
+        );
+        assert_eq!(
+            error("w KQkq - x 1"),
+            FenError::InvalidHalfmove("x".to_string())
+        );
+        assert_eq!(
+            error("w KQkq - -1 1"),
+            FenError::InvalidHalfmove("-1".to_string())
+        );
+        assert_eq!(
+            error("w KQkq - 0 one"),
+            FenError::InvalidFullmove("one".to_string())
+        );
+        assert_eq!(
+            error("w KQkq - 0 1 extra"),
+            FenError::ExtraField("extra".to_string())
+        );
+    }
+
+    #[test]
+    fn missing_fields_default() {
+        let config =
+            BoardConfig::try_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR").unwrap();
+        assert_eq!(config.get_active_color(), Color::White);
+        assert!(!config.can_castle(Color::White, CastleType::KingSide));
+        assert_eq!(config.get_en_passant_target(), None);
+    }
+
+    #[test]
+    fn castling_field() {
+        let config = BoardConfig::try_from_fen(START_FEN).unwrap();
+        for color in [Color::White, Color::Black] {
+            assert!(config.can_castle(color, CastleType::KingSide));
+            assert!(config.can_castle(color, CastleType::QueenSide));
+        }
+        assert_eq!(config.get_fen(), START_FEN);
+
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 5 20";
+        let config = BoardConfig::try_from_fen(fen).unwrap();
+        assert!(config.can_castle(Color::White, CastleType::KingSide));
+        assert!(!config.can_castle(Color::White, CastleType::QueenSide));
+        assert!(!config.can_castle(Color::Black, CastleType::KingSide));
+        assert!(config.can_castle(Color::Black, CastleType::QueenSide));
+        assert_eq!(config.get_fen(), fen);
+    }
+
+    #[test]
+    fn standard_castle_rooks() {
+        let config = BoardConfig::try_from_fen(START_FEN).unwrap();
+        assert!(!config.is_chess960());
+        assert_eq!(
+            config.get_castle_rook(Color::White, CastleType::QueenSide),
+            Square::A1
+        );
+        assert_eq!(
+            config.get_castle_rook(Color::Black, CastleType::KingSide),
+            Square::H8
+        );
+    }
+
+    #[test]
+    fn x_fen_castling() {
+        // The king is not on the e-file so `KQkq` name the outermost rooks
+        let fen = "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1";
+        let config = BoardConfig::try_from_fen(fen).unwrap();
+        assert!(config.is_chess960());
+        assert_eq!(
+            config.get_castle_rook(Color::White, CastleType::KingSide),
+            Square::H1
+        );
+        assert_eq!(
+            config.get_castle_rook(Color::White, CastleType::QueenSide),
+            Square::F1
+        );
+        assert_eq!(config.get_fen(), fen);
+
+        // Inner rooks need their file to tell them apart from the outer ones
+        let fen = "rr2k3/8/8/8/8/8/8/RR2K3 w Bb - 0 1";
+        let config = BoardConfig::try_from_fen(fen).unwrap();
+        assert!(config.is_chess960());
+        assert_eq!(
+            config.get_castle_rook(Color::White, CastleType::QueenSide),
+            Square::B1
+        );
+        assert_eq!(
+            config.get_castle_rook(Color::Black, CastleType::QueenSide),
+            Square::B8
+        );
+        assert_eq!(config.get_fen(), fen);
+
+        let config = BoardConfig::try_from_fen("rr2k3/8/8/8/8/8/8/RR2K3 w Qq - 0 1").unwrap();
+        assert!(!config.is_chess960());
+        assert_eq!(
+            config.get_castle_rook(Color::White, CastleType::QueenSide),
+            Square::A1
+        );
+    }
+
+    #[test]
+    fn shredder_fen_castling() {
+        let config =
+            BoardConfig::try_from_fen("1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w GBgb - 0 1")
+                .unwrap();
+        assert!(config.is_chess960());
+        assert_eq!(
+            config.get_castle_rook(Color::White, CastleType::KingSide),
+            Square::G1
+        );
+        assert_eq!(
+            config.get_castle_rook(Color::White, CastleType::QueenSide),
+            Square::B1
+        );
+        assert_eq!(
+            config.get_castle_rook(Color::Black, CastleType::KingSide),
+            Square::G8
+        );
+        assert_eq!(
+            config.get_castle_rook(Color::Black, CastleType::QueenSide),
+            Square::B8
+        );
+        // Written back as X-FEN, which reads into the same position
+        let fen = config.get_fen();
+        assert_eq!(
+            fen,
+            "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w KQkq - 0 1"
+        );
+        let again = BoardConfig::try_from_fen(&fen).unwrap();
+        assert_eq!(
+            again.get_castle_rook(Color::Black, CastleType::QueenSide),
+            Square::B8
+        );
+        assert!(again.is_chess960());
+
+        // Only files of the home rank make sense without a king to compare with
+        assert_eq!(
+            fen_error("8/8/8/8/8/8/8/1R4R1 w G - 0 1"),
+            FenError::InvalidCastle('G')
+        );
+    }
+
+    #[test]
+    fn castle_file_of_king() {
+        // A rook can not share the file of its king, reading it as either
+        // side would move the king off its square when castling.
+        assert_eq!(
+            fen_error("4k3/8/8/8/8/8/8/R3K2R w E - 0 1"),
+            FenError::InvalidCastle('E')
+        );
+        assert_eq!(
+            fen_error("r3k2r/8/8/8/8/8/8/R3K2R w KQe - 0 1"),
+            FenError::InvalidCastle('e')
+        );
+    }
+
+    #[test]
+    fn chess960_round_trip() {
+        for index in [0, 1, 100, 518, 959] {
+            let config = BoardConfig::from_chess960_index(index).unwrap();
+            let fen = config.get_fen();
+            let mut again = BoardConfig::try_from_fen(&fen).unwrap();
+            again.set_chess960(true);
+            assert_eq!(again.get_fen(), fen, "index {}", index);
+            for color in [Color::White, Color::Black] {
+                for side in [CastleType::KingSide, CastleType::QueenSide] {
+                    assert_eq!(
+                        again.get_castle_rook(color, side),
+                        config.get_castle_rook(color, side)
+                    );
+                }
+            }
+        }
     }
 }
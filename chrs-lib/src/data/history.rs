@@ -0,0 +1,82 @@
+use std::ops::Deref;
+
+use super::moves::{Move, MoveCommit};
+
+/// Moves played on a [`BoardConfig`](super::BoardConfig), oldest first. Each
+/// commit keeps what is needed to take the move back exactly.
+///
+/// Moves that are taken back are kept so they can be replayed, until a
+/// different move is played in their place.
+#[derive(Debug, Clone, Default)]
+pub struct MoveHistory {
+    commits: Vec<MoveCommit>,
+    // Taken back moves, the next one to replay last
+    undone: Vec<Move>,
+}
+
+impl Deref for MoveHistory {
+    type Target = [MoveCommit];
+
+    fn deref(&self) -> &Self::Target {
+        &self.commits
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveHistory {
+    type Item = &'a MoveCommit;
+    type IntoIter = std::slice::Iter<'a, MoveCommit>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.commits.iter()
+    }
+}
+
+impl MoveHistory {
+    /// Replaying the next taken back move keeps the rest of them, any other
+    /// move discards them.
+    pub fn push(&mut self, commit: MoveCommit) {
+        if self.undone.last() == Some(&commit.m) {
+            self.undone.pop();
+        } else {
+            self.undone.clear();
+        }
+        self.commits.push(commit);
+    }
+
+    /// Take back the last move, it can be replayed with
+    /// [`BoardConfig::redo`](super::BoardConfig::redo).
+    pub fn pop(&mut self) -> Option<MoveCommit> {
+        let commit = self.commits.pop()?;
+        self.undone.push(commit.m);
+        Some(commit)
+    }
+
+    pub fn clear(&mut self) {
+        self.commits.clear();
+        self.undone.clear();
+    }
+
+    /// Forget the taken back moves.
+    pub fn clear_undone(&mut self) {
+        self.undone.clear();
+    }
+
+    pub fn moves(&self) -> impl DoubleEndedIterator<Item = Move> + '_ {
+        self.commits.iter().map(|c| c.m)
+    }
+
+    /// Taken back moves in the order they would be replayed.
+    pub fn undone(&self) -> impl DoubleEndedIterator<Item = Move> + '_ {
+        self.undone.iter().rev().copied()
+    }
+
+    /// The move `BoardConfig::redo` would replay.
+    pub fn next_undone(&self) -> Option<Move> {
+        self.undone.last().copied()
+    }
+
+    /// Hashes of the positions the moves were played from.
+    pub fn hashes(&self) -> impl DoubleEndedIterator<Item = u64> + '_ {
+        self.commits.iter().map(|c| c.hash)
+    }
+}
@@ -1,20 +1,28 @@
 pub mod bitboard;
+mod chess960;
+mod draw;
 mod fen;
+mod history;
 mod moves;
 pub mod piece;
+mod san;
 mod square;
+mod validate;
 
 use crate::zobrist::{hash, update_castle, update_ep, update_side};
 use crate::{generator::MoveGenerator, zobrist::update_piece};
 use fen::Fen;
-use moves::CastleType;
 use std::str::FromStr;
 use strum::IntoEnumIterator;
 
 pub use bitboard::BitBoard;
-pub use moves::{List, Move, MoveCommit, MoveList, MoveType};
+pub use fen::FenError;
+pub use history::MoveHistory;
+pub use moves::{CastleType, Move, MoveCommit, MoveList, MoveParseError, MoveType, NullMoveCommit};
 pub use piece::{BoardPiece, Color, B_PIECES, W_PIECES};
+pub use san::SanError;
 pub use square::Square;
+pub use validate::PositionError;
 
 pub type BoardMap = [BitBoard; 12];
 
@@ -23,6 +31,33 @@ pub enum GameState {
     InPlay,
     Mate(Color),
     StaleMate,
+    ThreefoldRepetition,
+    FivefoldRepetition,
+    FiftyMoveRule,
+    SeventyFiveMoveRule,
+    InsufficientMaterial,
+}
+
+impl GameState {
+    pub fn is_draw(&self) -> bool {
+        !matches!(self, GameState::InPlay | GameState::Mate(_))
+    }
+}
+
+impl std::fmt::Display for GameState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use GameState::*;
+        match self {
+            InPlay => write!(f, "In Play"),
+            Mate(c) => write!(f, "Check Mate: {}", c),
+            StaleMate => write!(f, "It's a Stalemate"),
+            ThreefoldRepetition => write!(f, "Draw: Threefold Repetition"),
+            FivefoldRepetition => write!(f, "Draw: Fivefold Repetition"),
+            FiftyMoveRule => write!(f, "Draw: Fifty-Move Rule"),
+            SeventyFiveMoveRule => write!(f, "Draw: Seventy-Five-Move Rule"),
+            InsufficientMaterial => write!(f, "Draw: Insufficient Material"),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -30,17 +65,31 @@ pub struct BoardConfig {
     active_color: Color,
     en_passant_target: Option<Square>,
     castle_flags: CastleFlags,
-    halfmove_clock: u8,
-    fullmove_number: u8,
+    // Home squares of the castling rooks, indexed like the `CastleFlags` bits
+    castle_rooks: [Square; 4],
+    chess960: bool,
+    halfmove_clock: u16,
+    fullmove_number: u16,
     pub bitboards: BoardMap,
-    pub move_history: Box<List<MoveCommit>>,
+    pub move_history: MoveHistory,
     pub state: GameState,
     hash: u64,
+    // Key of the pawns alone, kept up to date with `hash`
+    pawn_hash: u64,
 }
 
 impl Default for BoardConfig {
     fn default() -> Self {
         Fen::make_config_from_str("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1")
+            .unwrap()
+    }
+}
+
+impl FromStr for BoardConfig {
+    type Err = FenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Fen::make_config_from_str(s)
     }
 }
 
@@ -49,6 +98,10 @@ impl BoardConfig {
         self.hash
     }
 
+    pub fn get_pawn_hash(&self) -> u64 {
+        self.pawn_hash
+    }
+
     pub fn print_board(&self) {
         println!("{}", self.to_string());
     }
@@ -101,7 +154,7 @@ impl BoardConfig {
     }
 
     pub fn get_last_commit(&self) -> Option<MoveCommit> {
-        self.move_history.get_last()
+        self.move_history.last().copied()
     }
 
     pub fn apply_move(&mut self, m: Move) {
@@ -130,6 +183,8 @@ impl BoardConfig {
 
         let prev_ep_target = self.en_passant_target;
         let prev_castle_flags = self.castle_flags;
+        let prev_halfmove_clock = self.halfmove_clock;
+        let prev_hash = self.hash;
 
         use MoveType::*;
         match m.move_type {
@@ -152,22 +207,18 @@ impl BoardConfig {
             self.clear_ep_target();
         }
         // castling state update
-        if m.from == Square::A1 || m.to == Square::A1 {
-            self.castle_flags.unset_white_ooo();
-        } else if m.from == Square::A8 || m.to == Square::A8 {
-            self.castle_flags.unset_black_ooo();
-        } else if m.from == Square::H1 || m.to == Square::H1 {
-            self.castle_flags.unset_white_oo();
-        } else if m.from == Square::H8 || m.to == Square::H8 {
-            self.castle_flags.unset_black_oo();
+        for color in [Color::White, Color::Black] {
+            for side in [CastleType::KingSide, CastleType::QueenSide] {
+                let rook = self.get_castle_rook(color, side);
+                if m.from == rook || m.to == rook {
+                    self.castle_flags.unset(color, side);
+                }
+            }
         }
-
-        if m.from == Square::E1 || m.to == Square::E1 {
-            self.castle_flags.unset_white_oo();
-            self.castle_flags.unset_white_ooo();
-        } else if m.from == Square::E8 || m.to == Square::E8 {
-            self.castle_flags.unset_black_oo();
-            self.castle_flags.unset_black_ooo();
+        if matches!(m.p, BoardPiece::WhiteKing | BoardPiece::BlackKing) {
+            let color = m.p.get_color();
+            self.castle_flags.unset(color, CastleType::KingSide);
+            self.castle_flags.unset(color, CastleType::QueenSide);
         }
 
         let castledelta = self.castle_flags.0 ^ prev_castle_flags.0;
@@ -178,9 +229,48 @@ impl BoardConfig {
         update_castle(prev_castle_flags.raw(), &mut self.hash);
         update_castle(self.castle_flags.raw(), &mut self.hash);
 
-        self.halfmove_clock += 1;
+        let is_pawn = matches!(m.p, BoardPiece::WhitePawn | BoardPiece::BlackPawn);
+        if is_pawn || m.capture.is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        }
+        self.toggle_active_color();
+        Some(MoveCommit::new(
+            m,
+            prev_ep_target,
+            CastleFlags(castledelta),
+            prev_halfmove_clock,
+            prev_hash,
+        ))
+    }
+
+    /// Pass the turn to the other side without moving, as used by null move
+    /// pruning. The en passant target is lost and the halfmove clock starts
+    /// over, positions before a null move can not be repeated after it.
+    pub fn make_null_move(&mut self) -> NullMoveCommit {
+        let commit = NullMoveCommit {
+            ep_target: self.en_passant_target,
+            halfmove_clock: self.halfmove_clock,
+            hash: self.hash,
+        };
+        self.clear_ep_target();
+        if self.active_color == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.halfmove_clock = 0;
+        self.toggle_active_color();
+        commit
+    }
+
+    pub fn undo_null_move(&mut self, commit: &NullMoveCommit) {
         self.toggle_active_color();
-        Some(MoveCommit::new(m, prev_ep_target, CastleFlags(castledelta)))
+        if self.active_color == Color::Black {
+            self.fullmove_number -= 1;
+        }
+        self.en_passant_target = commit.ep_target;
+        self.halfmove_clock = commit.halfmove_clock;
+        self.hash = commit.hash;
     }
 
     fn make_normal(&mut self, m: &Move) {
@@ -218,39 +308,20 @@ impl BoardConfig {
 
     fn make_castle(&mut self, m: &Move, castle_type: CastleType) {
         let pcolor = m.p.get_color();
-        match castle_type {
-            CastleType::KingSide => {
-                if pcolor == Color::White {
-                    self.move_piece(BoardPiece::WhiteKing, Square::E1, Square::G1);
-                    self.move_piece(BoardPiece::WhiteRook, Square::H1, Square::F1);
-                }
-                if pcolor == Color::Black {
-                    self.move_piece(BoardPiece::BlackKing, Square::E8, Square::G8);
-                    self.move_piece(BoardPiece::BlackRook, Square::H8, Square::F8);
-                }
-            }
-            CastleType::QueenSide => {
-                if pcolor == Color::White {
-                    self.move_piece(BoardPiece::WhiteKing, Square::E1, Square::C1);
-                    self.move_piece(BoardPiece::WhiteRook, Square::A1, Square::D1);
-                }
-                if pcolor == Color::Black {
-                    self.move_piece(BoardPiece::BlackKing, Square::E8, Square::C8);
-                    self.move_piece(BoardPiece::BlackRook, Square::A8, Square::D8);
-                }
-            }
-        }
+        let rook = match pcolor {
+            Color::White => BoardPiece::WhiteRook,
+            Color::Black => BoardPiece::BlackRook,
+        };
+        // In Chess960 the king or rook may already stand on the other's
+        // destination, so both leave the board before they are put back.
+        let (king_to, rook_to) = castle_type.destinations(pcolor);
+        self.remove_piece(m.p, m.from);
+        self.remove_piece(rook, m.to);
+        self.add_piece(m.p, king_to);
+        self.add_piece(rook, rook_to);
 
-        match pcolor {
-            Color::White => {
-                self.castle_flags.unset_white_oo();
-                self.castle_flags.unset_white_ooo();
-            }
-            Color::Black => {
-                self.castle_flags.unset_black_oo();
-                self.castle_flags.unset_black_ooo();
-            }
-        }
+        self.castle_flags.unset(pcolor, CastleType::KingSide);
+        self.castle_flags.unset(pcolor, CastleType::QueenSide);
     }
 
     fn make_promotion(&mut self, m: &Move, prom: BoardPiece) {
@@ -268,6 +339,31 @@ impl BoardConfig {
         self.state = GameState::InPlay;
     }
 
+    /// Replay the last move taken back with `undo`.
+    pub fn redo(&mut self) {
+        if let Some(m) = self.move_history.next_undone() {
+            match self.make_move(m) {
+                Some(commit) => self.move_history.push(commit),
+                // Only if the position was changed behind the history's back
+                None => self.move_history.clear_undone(),
+            }
+        }
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.move_history.next_undone().is_some()
+    }
+
+    /// Take back or replay moves until `ply` moves of the game are played.
+    pub fn goto_ply(&mut self, ply: usize) {
+        while self.move_history.len() > ply {
+            self.undo();
+        }
+        while self.move_history.len() < ply && self.can_redo() {
+            self.redo();
+        }
+    }
+
     pub fn undo_commit(&mut self, commit: &MoveCommit) {
         let pcolor = commit.m.p.get_color();
 
@@ -299,8 +395,9 @@ impl BoardConfig {
         update_castle(self.castle_flags.raw(), &mut self.hash);
         update_castle(oldcastleflags, &mut self.hash);
         self.castle_flags = CastleFlags(oldcastleflags);
-        self.halfmove_clock -= 1;
+        self.halfmove_clock = commit.halfmove_clock;
         self.toggle_active_color();
+        self.hash = commit.hash;
     }
 
     fn undo_normal(&mut self, commit: &MoveCommit) {
@@ -315,28 +412,17 @@ impl BoardConfig {
     }
 
     fn undo_castle(&mut self, commit: &MoveCommit, castle_type: CastleType) {
-        match commit.m.p.get_color() {
-            Color::White => match castle_type {
-                CastleType::KingSide => {
-                    self.move_piece(BoardPiece::WhiteKing, Square::G1, Square::E1);
-                    self.move_piece(BoardPiece::WhiteRook, Square::F1, Square::H1);
-                }
-                CastleType::QueenSide => {
-                    self.move_piece(BoardPiece::WhiteKing, Square::C1, Square::E1);
-                    self.move_piece(BoardPiece::WhiteRook, Square::D1, Square::A1);
-                }
-            },
-            Color::Black => match castle_type {
-                CastleType::KingSide => {
-                    self.move_piece(BoardPiece::BlackKing, Square::G8, Square::E8);
-                    self.move_piece(BoardPiece::BlackRook, Square::F8, Square::H8);
-                }
-                CastleType::QueenSide => {
-                    self.move_piece(BoardPiece::BlackKing, Square::C8, Square::E8);
-                    self.move_piece(BoardPiece::BlackRook, Square::D8, Square::A8);
-                }
-            },
-        }
+        let m = commit.m;
+        let pcolor = m.p.get_color();
+        let rook = match pcolor {
+            Color::White => BoardPiece::WhiteRook,
+            Color::Black => BoardPiece::BlackRook,
+        };
+        let (king_to, rook_to) = castle_type.destinations(pcolor);
+        self.remove_piece(m.p, king_to);
+        self.remove_piece(rook, rook_to);
+        self.add_piece(m.p, m.from);
+        self.add_piece(rook, m.to);
     }
 
     fn undo_promotion(&mut self, commit: &MoveCommit, prom: BoardPiece) {
@@ -364,12 +450,29 @@ impl BoardConfig {
         *self = BoardConfig::default();
     }
 
+    /// Panics if `s` is not a valid FEN string, use [`BoardConfig::try_from_fen`]
+    /// for untrusted input.
     pub fn from_fen_str(s: &str) -> Self {
+        Self::try_from_fen(s).unwrap_or_else(|e| panic!("Fen Error: {}", e))
+    }
+
+    pub fn try_from_fen(s: &str) -> Result<Self, FenError> {
         Fen::make_config_from_str(s)
     }
 
-    pub fn load_fen(&mut self, s: &str) {
-        *self = Fen::make_config_from_str(s);
+    /// Like [`BoardConfig::try_from_fen`] but also rejects positions that
+    /// fail [`BoardConfig::validate`].
+    pub fn try_from_fen_strict(s: &str) -> Result<Self, FenError> {
+        let config = Fen::make_config_from_str(s)?;
+        config.validate().map_err(FenError::IllegalPosition)?;
+        Ok(config)
+    }
+
+    /// Replaces the current position. The board is left untouched if `s` is
+    /// not a valid FEN string.
+    pub fn load_fen(&mut self, s: &str) -> Result<(), FenError> {
+        *self = Fen::make_config_from_str(s)?;
+        Ok(())
     }
 
     pub fn get_fen(&self) -> String {
@@ -405,15 +508,35 @@ impl BoardConfig {
         self.castle_flags.can_black_oo()
     }
 
+    /// Home square of the rook used to castle to `side`.
+    pub fn get_castle_rook(&self, color: Color, side: CastleType) -> Square {
+        self.castle_rooks[CastleFlags::index(color, side)]
+    }
+
+    pub fn can_castle(&self, color: Color, side: CastleType) -> bool {
+        self.castle_flags.can(color, side)
+    }
+
+    pub fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+
+    /// In Chess960 mode castling rights are written in X-FEN and a king's
+    /// two square move is no longer read as castling. The mode is turned on
+    /// when a FEN needs it.
+    pub fn set_chess960(&mut self, chess960: bool) {
+        self.chess960 = chess960;
+    }
+
     pub fn get_en_passant_target(&self) -> Option<Square> {
         self.en_passant_target
     }
 
-    pub fn get_halfmove_clock(&self) -> u8 {
+    pub fn get_halfmove_clock(&self) -> u16 {
         self.halfmove_clock
     }
 
-    pub fn get_fullmove_number(&self) -> u8 {
+    pub fn get_fullmove_number(&self) -> u16 {
         self.fullmove_number
     }
 
@@ -472,11 +595,17 @@ impl BoardConfig {
     fn remove_piece(&mut self, p: BoardPiece, from: Square) {
         self.remove_from_bitboard(p, from);
         update_piece(from, p, &mut self.hash);
+        if p.is_pawn() {
+            update_piece(from, p, &mut self.pawn_hash);
+        }
     }
 
     fn add_piece(&mut self, p: BoardPiece, to: Square) {
         self.add_to_bitboard(p, to);
         update_piece(to, p, &mut self.hash);
+        if p.is_pawn() {
+            update_piece(to, p, &mut self.pawn_hash);
+        }
     }
 
     fn toggle_active_color(&mut self) {
@@ -498,6 +627,28 @@ impl BoardConfig {
 pub struct CastleFlags(u8);
 
 impl CastleFlags {
+    // Bit of the castling right, also used to index the castling rooks
+    fn index(color: Color, side: CastleType) -> usize {
+        match (color, side) {
+            (Color::White, CastleType::KingSide) => 0,
+            (Color::White, CastleType::QueenSide) => 1,
+            (Color::Black, CastleType::KingSide) => 2,
+            (Color::Black, CastleType::QueenSide) => 3,
+        }
+    }
+
+    pub fn can(&self, color: Color, side: CastleType) -> bool {
+        self.0 & (1 << Self::index(color, side)) > 0
+    }
+
+    pub fn set(&mut self, color: Color, side: CastleType) {
+        self.0 |= 1 << Self::index(color, side);
+    }
+
+    pub fn unset(&mut self, color: Color, side: CastleType) {
+        self.0 &= !(1 << Self::index(color, side));
+    }
+
     pub fn can_white_oo(&self) -> bool {
         self.0 & 1 > 0
     }
@@ -1,8 +1,10 @@
-use super::piece::BoardPiece;
+use super::piece::{BoardPiece, Color};
 use super::square::Square;
 use super::{BoardConfig, CastleFlags};
+use crate::generator::MoveGenerator;
 use std::fmt::{Debug, Display, Formatter, Result};
 use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
 
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 pub enum CastleType {
@@ -10,6 +12,20 @@ pub enum CastleType {
     QueenSide,
 }
 
+impl CastleType {
+    /// Squares the king and the rook end up on, these are the same in
+    /// standard chess and Chess960.
+    pub fn destinations(&self, color: Color) -> (Square, Square) {
+        use Square::*;
+        match (color, self) {
+            (Color::White, CastleType::KingSide) => (G1, F1),
+            (Color::White, CastleType::QueenSide) => (C1, D1),
+            (Color::Black, CastleType::KingSide) => (G8, F8),
+            (Color::Black, CastleType::QueenSide) => (C8, D8),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum MoveType {
     Normal,
@@ -89,18 +105,20 @@ impl Move {
         let mut move_type: MoveType = Normal;
         let mut capture = c.get_at_sq(to);
 
-        // Castling
-        if p == BoardPiece::WhiteKing {
-            if from == Square::E1 && to == Square::G1 {
-                move_type = Castle(CastleType::KingSide);
-            } else if from == Square::E1 && to == Square::C1 {
-                move_type = Castle(CastleType::QueenSide);
-            }
-        } else if p == BoardPiece::BlackKing {
-            if from == Square::E8 && to == Square::G8 {
-                move_type = Castle(CastleType::KingSide);
-            } else if from == Square::E8 && to == Square::C8 {
-                move_type = Castle(CastleType::QueenSide);
+        // Castling is encoded as the king taking its own rook. In standard
+        // chess the two square king move is understood as well.
+        if p == BoardPiece::WhiteKing || p == BoardPiece::BlackKing {
+            let color = p.get_color();
+            for side in [CastleType::KingSide, CastleType::QueenSide] {
+                if !c.castle_flags.can(color, side) {
+                    continue;
+                }
+                let rook = c.get_castle_rook(color, side);
+                let (king_to, _) = side.destinations(color);
+                let two_squares = (from as i32 - to as i32).abs() == 2;
+                if to == rook || (!c.is_chess960() && two_squares && to == king_to) {
+                    return Self::new(from, rook, p, None, Castle(side));
+                }
             }
         }
         // Pawn: Double Push, En Passant and Promotion
@@ -138,6 +156,76 @@ impl Move {
         }
     }
 
+    /// Long algebraic notation as used by the UCI protocol, e.g. `e7e8q`.
+    /// Castling is written as the king's two square move.
+    pub fn to_uci(&self) -> String {
+        match self.move_type {
+            MoveType::Castle(side) => {
+                let (king_to, _) = side.destinations(self.p.get_color());
+                format!("{}{}", self.from, king_to).to_lowercase()
+            }
+            _ => self.to_string().to_lowercase(),
+        }
+    }
+
+    /// UCI notation in Chess960 mode, where castling is written as the king
+    /// taking its own rook, e.g. `e1h1`.
+    pub fn to_uci_chess960(&self) -> String {
+        self.to_string().to_lowercase()
+    }
+
+    /// Parses a move in UCI long algebraic notation and checks that it is
+    /// legal in the position `config`. Castling is accepted both as the
+    /// king's two square move and as the king taking its own rook.
+    pub fn from_uci(
+        s: &str,
+        config: &BoardConfig,
+        gen: &MoveGenerator,
+    ) -> std::result::Result<Move, MoveParseError> {
+        let invalid = || MoveParseError::Invalid(s.to_string());
+        if !s.is_ascii() || s.len() < 4 || s.len() > 5 {
+            return Err(invalid());
+        }
+
+        let from = Square::from_str(&s[0..2]).map_err(|_| invalid())?;
+        let to = Square::from_str(&s[2..4]).map_err(|_| invalid())?;
+        let prom = match s[4..].chars().next() {
+            Some(c) if "nbrqNBRQ".contains(c) => {
+                let c = match config.get_active_color() {
+                    Color::White => c.to_ascii_uppercase(),
+                    Color::Black => c.to_ascii_lowercase(),
+                };
+                Some(BoardPiece::from_str(&c.to_string()).unwrap())
+            }
+            Some(_) => return Err(invalid()),
+            None => None,
+        };
+
+        let mut config = config.clone();
+        let moves = gen.gen_all_moves(config.get_active_color(), &mut config, false);
+        let exact = moves.iter().find(|m| {
+            let m_prom = match m.move_type {
+                MoveType::Promotion(p) => p,
+                _ => None,
+            };
+            m.from == from && m.to == to && m_prom == prom
+        });
+        // A king move to the castling destination may also be a normal move
+        // in Chess960, so it is only read as castling when nothing else fits.
+        let castle = || {
+            moves.iter().find(|m| match m.move_type {
+                MoveType::Castle(side) => {
+                    m.from == from && side.destinations(m.p.get_color()).0 == to && prom.is_none()
+                }
+                _ => false,
+            })
+        };
+        exact
+            .or_else(castle)
+            .copied()
+            .ok_or_else(|| MoveParseError::Illegal(s.to_string()))
+    }
+
     pub fn is_prom(&self) -> bool {
         if let MoveType::Promotion(_) = self.move_type {
             return true;
@@ -159,11 +247,30 @@ impl Move {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveParseError {
+    Invalid(String),
+    Illegal(String),
+}
+
+impl Display for MoveParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            MoveParseError::Invalid(s) => write!(f, "'{}' is not a valid UCI move", s),
+            MoveParseError::Illegal(s) => write!(f, "'{}' is not a legal move", s),
+        }
+    }
+}
+
+impl std::error::Error for MoveParseError {}
+
 #[derive(Debug, Copy, Clone)]
 pub struct MoveCommit {
     pub m: Move,
     pub ep_target: Option<Square>,
     pub castledelta: CastleFlags,
+    pub halfmove_clock: u16,
+    pub hash: u64,
 }
 
 impl Display for MoveCommit {
@@ -186,79 +293,29 @@ impl Display for MoveCommit {
 }
 
 impl MoveCommit {
-    pub fn new(m: Move, ep_target: Option<Square>, castledelta: CastleFlags) -> Self {
+    pub fn new(
+        m: Move,
+        ep_target: Option<Square>,
+        castledelta: CastleFlags,
+        halfmove_clock: u16,
+        hash: u64,
+    ) -> Self {
         Self {
             m,
             ep_target,
             castledelta,
+            halfmove_clock,
+            hash,
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct List<T> {
-    pub list: [Option<T>; 255],
-    pub counter: u8,
-    capacity: u8,
-}
-
-impl<T: Copy> Default for List<T> {
-    fn default() -> Self {
-        List {
-            list: [None; 255],
-            counter: 0,
-            capacity: 255,
-        }
-    }
-}
-
-impl<T: Copy> List<T> {
-    pub fn clear(&mut self) {
-        self.counter = 0;
-    }
-
-    pub fn push(&mut self, m: T) {
-        if self.counter == self.capacity {
-            log::error!("MoveHistory is out of capacity");
-            panic!();
-        }
-        self.list[self.counter as usize] = Some(m);
-        self.counter += 1;
-    }
-
-    pub fn pop(&mut self) -> Option<T> {
-        if self.counter > 0 {
-            self.counter -= 1;
-        }
-        let r = self.list[self.counter as usize];
-        self.list[self.counter as usize] = None;
-        r
-    }
-
-    pub fn get_last(&self) -> Option<T> {
-        self.list[self.counter as usize]
-    }
-
-    pub fn data(&mut self) -> &mut [Option<T>] {
-        &mut self.list[..(self.counter as usize)]
-    }
-
-    pub fn len(&self) -> usize {
-        self.counter as usize
-    }
-
-    pub fn iter(&self) -> impl Iterator<Item = &Option<T>> {
-        self.list.iter().take(self.counter as usize)
-    }
-}
-
-impl List<Move> {
-    pub fn has_target_sq(&self, sq: Square) -> bool {
-        self.list
-            .iter()
-            .take(self.counter as usize)
-            .any(|x| x.unwrap().to == sq)
-    }
+/// State a null move destroys, to restore it afterwards.
+#[derive(Debug, Clone, Copy)]
+pub struct NullMoveCommit {
+    pub ep_target: Option<Square>,
+    pub halfmove_clock: u16,
+    pub hash: u64,
 }
 
 pub struct MoveList(pub Vec<Move>);
@@ -290,3 +347,133 @@ impl MoveList {
         Self(Vec::with_capacity(capacity))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::test_generator;
+
+    fn uci(s: &str, fen: &str) -> std::result::Result<Move, MoveParseError> {
+        Move::from_uci(s, &BoardConfig::from_fen_str(fen), test_generator())
+    }
+
+    #[test]
+    fn round_trip_all_legal_moves() {
+        let gen = test_generator();
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1",
+        ] {
+            let mut config = BoardConfig::from_fen_str(fen);
+            let moves = gen.gen_all_moves(config.get_active_color(), &mut config, false);
+            for m in moves.iter() {
+                assert_eq!(Move::from_uci(&m.to_uci(), &config, gen), Ok(*m));
+                assert_eq!(Move::from_uci(&m.to_uci_chess960(), &config, gen), Ok(*m));
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_moves() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        for s in [
+            "", "e2", "e2e", "e2e4e5", "e9e4", "i2e4", "e2-4", "e7e8x", "é2e4",
+        ] {
+            assert_eq!(
+                uci(s, start),
+                Err(MoveParseError::Invalid(s.to_string())),
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn illegal_moves() {
+        let start = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        // Too far, the other side's pawn, blocked castling and a promotion
+        // letter on a move that does not promote.
+        for s in ["e2e5", "e7e5", "e1g1", "e1h1", "e2e4q"] {
+            assert_eq!(
+                uci(s, start),
+                Err(MoveParseError::Illegal(s.to_string())),
+                "{}",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn promotion_letters() {
+        let fen = "3k4/4P3/8/8/8/8/8/4K3 w - - 0 1";
+        let m = uci("e7e8q", fen).unwrap();
+        assert_eq!((m.from, m.to), (Square::E7, Square::E8));
+        assert_eq!(
+            m.move_type,
+            MoveType::Promotion(Some(BoardPiece::WhiteQueen))
+        );
+        // Upper case is accepted as well
+        assert_eq!(
+            uci("e7e8N", fen).unwrap().move_type,
+            MoveType::Promotion(Some(BoardPiece::WhiteKnight))
+        );
+        // The piece to promote to is required
+        assert_eq!(
+            uci("e7e8", fen),
+            Err(MoveParseError::Illegal("e7e8".to_string()))
+        );
+
+        // The letter always names a piece of the side to move
+        let m = uci("e2e1r", "4k3/8/8/8/8/8/4p3/K7 b - - 0 1").unwrap();
+        assert_eq!(
+            m.move_type,
+            MoveType::Promotion(Some(BoardPiece::BlackRook))
+        );
+        assert_eq!(m.to_uci(), "e2e1r");
+    }
+
+    #[test]
+    fn castling_forms() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        for (s, side, rook) in [
+            ("e1g1", CastleType::KingSide, Square::H1),
+            ("e1h1", CastleType::KingSide, Square::H1),
+            ("e1c1", CastleType::QueenSide, Square::A1),
+            ("e1a1", CastleType::QueenSide, Square::A1),
+        ] {
+            let m = uci(s, fen).unwrap();
+            assert_eq!(m.move_type, MoveType::Castle(side), "{}", s);
+            assert_eq!((m.from, m.to), (Square::E1, rook), "{}", s);
+        }
+        let m = uci("e8c8", "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+        assert_eq!(m.move_type, MoveType::Castle(CastleType::QueenSide));
+        assert_eq!(
+            (m.to_uci(), m.to_uci_chess960()),
+            ("e8c8".into(), "e8a8".into())
+        );
+    }
+
+    #[test]
+    fn chess960_castling_forms() {
+        // The king's two square move would land on its own rook, only the
+        // castle fits.
+        let fen = "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w GBgb - 0 1";
+        for s in ["e1g1", "e1c1", "e1b1"] {
+            let m = uci(s, fen).unwrap();
+            assert!(matches!(m.move_type, MoveType::Castle(_)), "{}", s);
+        }
+        assert_eq!(uci("e1g1", fen).unwrap().to, Square::G1);
+        assert_eq!(uci("e1c1", fen).unwrap().to, Square::B1);
+
+        // The king already stands next to its destination, the two square
+        // form is a plain king move and castling has to take the rook.
+        let fen = "4k3/8/8/8/8/8/8/5K1R w H - 0 1";
+        let m = uci("f1g1", fen).unwrap();
+        assert_eq!(m.move_type, MoveType::Normal);
+        assert_eq!(m.to, Square::G1);
+        let m = uci("f1h1", fen).unwrap();
+        assert_eq!(m.move_type, MoveType::Castle(CastleType::KingSide));
+        assert_eq!(m.to, Square::H1);
+    }
+}
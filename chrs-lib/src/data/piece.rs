@@ -62,6 +62,10 @@ impl BoardPiece {
         }
     }
 
+    pub fn is_pawn(&self) -> bool {
+        matches!(self, BoardPiece::WhitePawn | BoardPiece::BlackPawn)
+    }
+
     pub fn utf_str(&self) -> &'static str {
         use BoardPiece::*;
         match self {
@@ -0,0 +1,348 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::generator::MoveGenerator;
+
+use super::moves::{CastleType, Move, MoveType};
+use super::piece::{BoardPiece, Color};
+use super::square::Square;
+use super::BoardConfig;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanError {
+    Empty,
+    Invalid(String),
+    Illegal(String),
+    Ambiguous(String),
+}
+
+impl Display for SanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use SanError::*;
+        match self {
+            Empty => write!(f, "empty move"),
+            Invalid(s) => write!(f, "'{}' is not a valid SAN move", s),
+            Illegal(s) => write!(f, "'{}' does not match any legal move", s),
+            Ambiguous(s) => write!(f, "'{}' matches more than one legal move", s),
+        }
+    }
+}
+
+impl std::error::Error for SanError {}
+
+fn piece_of_color(letter: char, color: Color) -> Option<BoardPiece> {
+    let letter = match color {
+        Color::White => letter.to_ascii_uppercase(),
+        Color::Black => letter.to_ascii_lowercase(),
+    };
+    BoardPiece::from_str(&letter.to_string()).ok()
+}
+
+impl Move {
+    /// Standard Algebraic Notation of this move in the position `config`.
+    /// The move is expected to be legal in that position.
+    pub fn to_san(&self, config: &BoardConfig, gen: &MoveGenerator) -> String {
+        let mut config = config.clone();
+        let mut s = String::new();
+
+        match self.move_type {
+            MoveType::Castle(CastleType::KingSide) => s.push_str("O-O"),
+            MoveType::Castle(CastleType::QueenSide) => s.push_str("O-O-O"),
+            _ => {
+                let (from_file, from_rank): (usize, usize) = self.from.into();
+                let from_name = self.from.to_string();
+                let is_pawn = matches!(self.p, BoardPiece::WhitePawn | BoardPiece::BlackPawn);
+
+                if is_pawn {
+                    if self.capture.is_some() {
+                        s.push_str(&from_name[..1]);
+                    }
+                } else {
+                    s.push_str(&self.p.to_string().to_uppercase());
+
+                    let others: Vec<Square> = gen
+                        .gen_all_moves(config.get_active_color(), &mut config, false)
+                        .iter()
+                        .filter(|m| m.p == self.p && m.to == self.to && m.from != self.from)
+                        .map(|m| m.from)
+                        .collect();
+                    if !others.is_empty() {
+                        let coords = |sq: &Square| -> (usize, usize) { (*sq).into() };
+                        let same_file = others.iter().any(|sq| coords(sq).0 == from_file);
+                        let same_rank = others.iter().any(|sq| coords(sq).1 == from_rank);
+                        if !same_file {
+                            s.push_str(&from_name[..1]);
+                        } else if !same_rank {
+                            s.push_str(&from_name[1..]);
+                        } else {
+                            s.push_str(&from_name);
+                        }
+                    }
+                }
+
+                if self.capture.is_some() {
+                    s.push('x');
+                }
+                s.push_str(&self.to.to_string());
+                if let MoveType::Promotion(Some(prom)) = self.move_type {
+                    s.push('=');
+                    s.push_str(&prom.to_string().to_uppercase());
+                }
+            }
+        }
+
+        if config.make_move(*self).is_some() {
+            let side = config.get_active_color();
+            if config.is_king_in_check(gen, side) {
+                if gen.gen_all_moves(side, &mut config, false).is_empty() {
+                    s.push('#');
+                } else {
+                    s.push('+');
+                }
+            }
+        }
+
+        s
+    }
+
+    /// Parses a move in Standard Algebraic Notation for the position
+    /// `config`. Common variations are accepted: zeros for castling,
+    /// missing or superfluous check marks, annotations, promotions without
+    /// `=` and fully specified origin squares.
+    pub fn from_san(s: &str, config: &BoardConfig, gen: &MoveGenerator) -> Result<Move, SanError> {
+        let original = s.trim();
+        let mut s = original.trim_end_matches(['+', '#', '!', '?']).to_string();
+        if let Some(stripped) = s.strip_suffix("e.p.") {
+            s = stripped.trim_end().to_string();
+        }
+        if s.is_empty() {
+            return Err(SanError::Empty);
+        }
+
+        let mut config = config.clone();
+        let side = config.get_active_color();
+        let moves = gen.gen_all_moves(side, &mut config, false);
+        let invalid = || SanError::Invalid(original.to_string());
+
+        let castle = match s.replace('0', "O").to_uppercase().as_str() {
+            "O-O" => Some(CastleType::KingSide),
+            "O-O-O" => Some(CastleType::QueenSide),
+            _ => None,
+        };
+        if let Some(castle) = castle {
+            return moves
+                .iter()
+                .find(|m| m.move_type == MoveType::Castle(castle))
+                .copied()
+                .ok_or_else(|| SanError::Illegal(original.to_string()));
+        }
+
+        let mut chars: Vec<char> = s
+            .chars()
+            .filter(|c| !matches!(c, 'x' | ':' | '-'))
+            .collect();
+
+        // Promotion piece, with or without the `=`
+        let mut prom = None;
+        if let Some(&last) = chars.last() {
+            if "NBRQnbrq".contains(last) {
+                prom = Some(piece_of_color(last, side).ok_or_else(invalid)?);
+                chars.pop();
+                if chars.last() == Some(&'=') {
+                    chars.pop();
+                }
+            }
+        }
+
+        let piece = match chars.first() {
+            Some(c) if "NBRQK".contains(*c) => {
+                let p = piece_of_color(*c, side).ok_or_else(invalid)?;
+                chars.remove(0);
+                p
+            }
+            _ => piece_of_color('P', side).unwrap(),
+        };
+
+        if chars.len() < 2 {
+            return Err(invalid());
+        }
+        let to: String = chars[chars.len() - 2..].iter().collect();
+        let to = Square::from_str(&to).map_err(|_| invalid())?;
+
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in &chars[..chars.len() - 2] {
+            match c {
+                'a'..='h' => from_file = Some(*c as usize - 'a' as usize),
+                '1'..='8' => from_rank = Some(*c as usize - '1' as usize),
+                _ => return Err(invalid()),
+            }
+        }
+
+        let candidates: Vec<Move> = moves
+            .iter()
+            .filter(|m| {
+                let (file, rank): (usize, usize) = m.from.into();
+                let m_prom = match m.move_type {
+                    MoveType::Promotion(p) => p,
+                    _ => None,
+                };
+                m.p == piece
+                    && m.to == to
+                    && !matches!(m.move_type, MoveType::Castle(_))
+                    && from_file.unwrap_or(file) == file
+                    && from_rank.unwrap_or(rank) == rank
+                    && m_prom == prom
+            })
+            .copied()
+            .collect();
+
+        match candidates.len() {
+            0 => Err(SanError::Illegal(original.to_string())),
+            1 => Ok(candidates[0]),
+            _ => Err(SanError::Ambiguous(original.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::test_generator;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const PROMOTIONS: &str = "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1";
+    const CHESS960: &str = "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w GBgb - 0 1";
+
+    fn uci(s: &str, config: &BoardConfig, gen: &MoveGenerator) -> Move {
+        Move::from_uci(s, config, gen).unwrap()
+    }
+
+    fn san(s: &str, config: &BoardConfig, gen: &MoveGenerator) -> Result<Move, SanError> {
+        Move::from_san(s, config, gen)
+    }
+
+    #[test]
+    fn round_trip_all_legal_moves() {
+        let gen = test_generator();
+        let startpos = BoardConfig::default().get_fen();
+        for fen in [startpos.as_str(), KIWIPETE, PROMOTIONS, CHESS960] {
+            let mut config = BoardConfig::from_fen_str(fen);
+            let moves = gen.gen_all_moves(config.get_active_color(), &mut config, false);
+            assert!(!moves.is_empty(), "no moves in {}", fen);
+            for m in moves.iter() {
+                let s = m.to_san(&config, gen);
+                assert_eq!(san(&s, &config, gen), Ok(*m), "{} in {}", s, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn chess960_castling() {
+        let gen = test_generator();
+        let config = BoardConfig::from_fen_str(CHESS960);
+        assert!(config.is_chess960());
+        let short = san("O-O", &config, gen).unwrap();
+        assert_eq!(short.move_type, MoveType::Castle(CastleType::KingSide));
+        assert_eq!(short.to, Square::G1);
+        let long = san("O-O-O", &config, gen).unwrap();
+        assert_eq!(long.move_type, MoveType::Castle(CastleType::QueenSide));
+        assert_eq!(long.to_san(&config, gen), "O-O-O");
+    }
+
+    #[test]
+    fn file_disambiguation() {
+        let gen = test_generator();
+        let config = BoardConfig::from_fen_str("4k3/8/8/8/8/5N2/8/1N2K3 w - - 0 1");
+        let m = uci("b1d2", &config, gen);
+        assert_eq!(m.to_san(&config, gen), "Nbd2");
+        assert_eq!(uci("f3d2", &config, gen).to_san(&config, gen), "Nfd2");
+        assert_eq!(san("Nbd2", &config, gen), Ok(m));
+        assert_eq!(
+            san("Nd2", &config, gen),
+            Err(SanError::Ambiguous("Nd2".to_string()))
+        );
+    }
+
+    #[test]
+    fn rank_disambiguation() {
+        let gen = test_generator();
+        let config = BoardConfig::from_fen_str("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1");
+        let m = uci("a1a3", &config, gen);
+        assert_eq!(m.to_san(&config, gen), "R1a3");
+        assert_eq!(uci("a5a3", &config, gen).to_san(&config, gen), "R5a3");
+        assert_eq!(san("R1a3", &config, gen), Ok(m));
+    }
+
+    #[test]
+    fn square_disambiguation() {
+        let gen = test_generator();
+        let config = BoardConfig::from_fen_str("4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1");
+        let m = uci("a1b2", &config, gen);
+        assert_eq!(m.to_san(&config, gen), "Qa1b2");
+        assert_eq!(san("Qa1b2", &config, gen), Ok(m));
+    }
+
+    #[test]
+    fn check_and_mate_suffixes() {
+        let gen = test_generator();
+        let config = BoardConfig::from_fen_str(
+            "rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2",
+        );
+        let mate = uci("d8h4", &config, gen);
+        assert_eq!(mate.to_san(&config, gen), "Qh4#");
+        assert_eq!(san("Qh4", &config, gen), Ok(mate));
+        assert_eq!(san("Qh4+", &config, gen), Ok(mate));
+
+        let config = BoardConfig::from_fen_str("4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+        let check = uci("a1a8", &config, gen);
+        assert_eq!(check.to_san(&config, gen), "Ra8+");
+        assert_eq!(san("Ra8", &config, gen), Ok(check));
+        assert_eq!(san("Ra8#", &config, gen), Ok(check));
+    }
+
+    #[test]
+    fn accepted_variants() {
+        let gen = test_generator();
+        let config = BoardConfig::default();
+        let nf3 = uci("g1f3", &config, gen);
+        assert_eq!(san("Nf3!?", &config, gen), Ok(nf3));
+        assert_eq!(san("Ng1f3", &config, gen), Ok(nf3));
+        assert_eq!(san("Ng1-f3", &config, gen), Ok(nf3));
+        assert_eq!(san("e2e4", &config, gen), Ok(uci("e2e4", &config, gen)));
+
+        let config = BoardConfig::from_fen_str(KIWIPETE);
+        let castle = san("0-0", &config, gen).unwrap();
+        assert_eq!(castle.move_type, MoveType::Castle(CastleType::KingSide));
+        assert_eq!(san("O-O", &config, gen), Ok(castle));
+        let castle = san("0-0-0", &config, gen).unwrap();
+        assert_eq!(castle.move_type, MoveType::Castle(CastleType::QueenSide));
+
+        let config = BoardConfig::from_fen_str("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+        let queen = uci("b7b8q", &config, gen);
+        assert_eq!(queen.to_san(&config, gen), "b8=Q+");
+        assert_eq!(san("b8Q", &config, gen), Ok(queen));
+        assert_eq!(san("b8=Q", &config, gen), Ok(queen));
+        assert_eq!(san("b8q", &config, gen), Ok(queen));
+    }
+
+    #[test]
+    fn errors() {
+        let gen = test_generator();
+        let config = BoardConfig::default();
+        assert_eq!(san("", &config, gen), Err(SanError::Empty));
+        assert_eq!(san("+", &config, gen), Err(SanError::Empty));
+        assert_eq!(
+            san("Zf3", &config, gen),
+            Err(SanError::Invalid("Zf3".to_string()))
+        );
+        assert_eq!(
+            san("Nf4", &config, gen),
+            Err(SanError::Illegal("Nf4".to_string()))
+        );
+        assert_eq!(
+            san("O-O", &config, gen),
+            Err(SanError::Illegal("O-O".to_string()))
+        );
+    }
+}
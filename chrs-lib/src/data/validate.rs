@@ -0,0 +1,347 @@
+use std::fmt::Display;
+
+use crate::generator::tables::{
+    generate_bishop_attack, generate_rook_attack, BP_ATK_TBL, K_ATK_TBL, N_ATK_TBL, WP_ATK_TBL,
+};
+
+use super::moves::CastleType;
+use super::piece::{BoardPiece, Color};
+use super::square::Square;
+use super::{BitBoard, BoardConfig};
+
+/// A rule of chess broken by a [`BoardConfig`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PositionError {
+    OverlappingPieces(Square),
+    KingCount { color: Color, count: u32 },
+    TooManyPawns { color: Color, count: u32 },
+    TooManyPieces { color: Color, count: u32 },
+    PawnOnBackRank(Square),
+    InvalidCastle { color: Color, side: CastleType },
+    InvalidEnPassant(Square),
+    OpponentInCheck(Color),
+}
+
+impl Display for PositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use PositionError::*;
+        match self {
+            OverlappingPieces(sq) => write!(f, "more than one piece on {}", sq),
+            KingCount { color, count } => write!(f, "{} has {} kings", color, count),
+            TooManyPawns { color, count } => write!(f, "{} has {} pawns", color, count),
+            TooManyPieces { color, count } => write!(f, "{} has {} pieces", color, count),
+            PawnOnBackRank(sq) => write!(f, "pawn on back rank square {}", sq),
+            InvalidCastle { color, side } => write!(
+                f,
+                "{} can not castle {:?}, king or rook is not on its home square",
+                color, side
+            ),
+            InvalidEnPassant(sq) => write!(f, "{} is not a valid en passant target", sq),
+            OpponentInCheck(color) => {
+                write!(f, "{} is in check but it is not their turn", color)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+const BACK_RANKS: u64 = 0xff000000000000ff;
+
+impl BoardConfig {
+    /// Checks that the position could occur in a game of chess. Every broken
+    /// rule is reported so callers can decide to reject or repair the setup.
+    pub fn validate(&self) -> Result<(), Vec<PositionError>> {
+        let mut errors = Vec::new();
+
+        let mut seen = 0u64;
+        for bb in self.bitboards.iter() {
+            let mut overlap = BitBoard::from(seen & **bb);
+            while let Some(sq) = overlap.pop_sq() {
+                errors.push(PositionError::OverlappingPieces(sq));
+            }
+            seen |= **bb;
+        }
+
+        for color in [Color::White, Color::Black] {
+            let (king, pawn, occupancy) = match color {
+                Color::White => (
+                    BoardPiece::WhiteKing,
+                    BoardPiece::WhitePawn,
+                    self.white_occupancy(),
+                ),
+                Color::Black => (
+                    BoardPiece::BlackKing,
+                    BoardPiece::BlackPawn,
+                    self.black_occupancy(),
+                ),
+            };
+
+            let count = self.bitboards[king as usize].count_ones();
+            if count != 1 {
+                errors.push(PositionError::KingCount { color, count });
+            }
+            let count = self.bitboards[pawn as usize].count_ones();
+            if count > 8 {
+                errors.push(PositionError::TooManyPawns { color, count });
+            }
+            let count = occupancy.count_ones();
+            if count > 16 {
+                errors.push(PositionError::TooManyPieces { color, count });
+            }
+
+            let mut back = BitBoard::from(*self.bitboards[pawn as usize] & BACK_RANKS);
+            while let Some(sq) = back.pop_sq() {
+                errors.push(PositionError::PawnOnBackRank(sq));
+            }
+        }
+
+        use BoardPiece::*;
+        for color in [Color::White, Color::Black] {
+            let (king, rook, rank) = match color {
+                Color::White => (WhiteKing, WhiteRook, 0),
+                Color::Black => (BlackKing, BlackRook, 7),
+            };
+            let king_file = (0..8).find(|f: &usize| {
+                self.bitboards[king as usize].is_set((*f, rank).try_into().unwrap())
+            });
+            for side in [CastleType::KingSide, CastleType::QueenSide] {
+                if !self.can_castle(color, side) {
+                    continue;
+                }
+                let rook_sq = self.get_castle_rook(color, side);
+                let (rook_file, _): (usize, usize) = rook_sq.into();
+                let valid = match king_file {
+                    Some(k) => {
+                        self.bitboards[rook as usize].is_set(rook_sq)
+                            && (self.is_chess960() || k == 4)
+                            && match side {
+                                CastleType::KingSide => rook_file > k,
+                                CastleType::QueenSide => rook_file < k,
+                            }
+                    }
+                    None => false,
+                };
+                if !valid {
+                    errors.push(PositionError::InvalidCastle { color, side });
+                }
+            }
+        }
+
+        if let Some(t) = self.en_passant_target {
+            // The pawn that just made a double push stands in front of the
+            // target and both the target and the square it came from are empty.
+            let (rank, pawn, forward) = match self.active_color {
+                Color::White => (5, BlackPawn, -8),
+                Color::Black => (2, WhitePawn, 8),
+            };
+            let all = self.all_occupancy();
+            let valid = t as usize / 8 == rank && {
+                let pushed = Square::try_from((t as i32 + forward) as usize).unwrap();
+                let origin = Square::try_from((t as i32 - forward) as usize).unwrap();
+                self.bitboards[pawn as usize].is_set(pushed)
+                    && !all.is_set(t)
+                    && !all.is_set(origin)
+            };
+            if !valid {
+                errors.push(PositionError::InvalidEnPassant(t));
+            }
+        }
+
+        let waiting = !self.active_color;
+        let king = match waiting {
+            Color::White => WhiteKing,
+            Color::Black => BlackKing,
+        };
+        let kings = self.bitboards[king as usize];
+        if kings.count_ones() == 1 && self.is_attacked_by(kings.peek().unwrap(), self.active_color)
+        {
+            errors.push(PositionError::OpponentInCheck(waiting));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    // Attack detection that does not need the magic tables of the
+    // `MoveGenerator`, only meant for the occasional validation.
+    fn is_attacked_by(&self, sq: Square, color: Color) -> bool {
+        use BoardPiece::*;
+        let (pawn_atk, pieces) = match color {
+            Color::White => (
+                BP_ATK_TBL[sq as usize],
+                [
+                    WhitePawn,
+                    WhiteKnight,
+                    WhiteBishop,
+                    WhiteRook,
+                    WhiteQueen,
+                    WhiteKing,
+                ],
+            ),
+            Color::Black => (
+                WP_ATK_TBL[sq as usize],
+                [
+                    BlackPawn,
+                    BlackKnight,
+                    BlackBishop,
+                    BlackRook,
+                    BlackQueen,
+                    BlackKing,
+                ],
+            ),
+        };
+        let [pawn, knight, bishop, rook, queen, king] = pieces.map(|p| *self.bitboards[p as usize]);
+        let all = *self.all_occupancy();
+
+        pawn_atk & pawn > 0
+            || N_ATK_TBL[sq as usize] & knight > 0
+            || K_ATK_TBL[sq as usize] & king > 0
+            || generate_bishop_attack(sq as usize, all) & (bishop | queen) > 0
+            || generate_rook_attack(sq as usize, all) & (rook | queen) > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::FenError;
+
+    fn errors(fen: &str) -> Vec<PositionError> {
+        BoardConfig::from_fen_str(fen)
+            .validate()
+            .err()
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn legal_positions() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3",
+            "4k3/8/8/8/8/8/8/4K2R b K - 0 1",
+        ] {
+            assert_eq!(errors(fen), vec![], "{}", fen);
+        }
+    }
+
+    #[test]
+    fn king_count() {
+        assert_eq!(
+            errors("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+            vec![PositionError::KingCount {
+                color: Color::Black,
+                count: 0
+            }]
+        );
+        assert_eq!(
+            errors("4k3/8/8/8/8/8/8/3KK3 w - - 0 1"),
+            vec![PositionError::KingCount {
+                color: Color::White,
+                count: 2
+            }]
+        );
+    }
+
+    #[test]
+    fn too_many_pawns_and_pieces() {
+        assert_eq!(
+            errors("4k3/8/8/8/8/P7/PPPPPPPP/4K3 w - - 0 1"),
+            vec![PositionError::TooManyPawns {
+                color: Color::White,
+                count: 9
+            }]
+        );
+        assert_eq!(
+            errors("nnnnknnn/pppppppp/n7/8/8/8/8/4K3 w - - 0 1"),
+            vec![PositionError::TooManyPieces {
+                color: Color::Black,
+                count: 17
+            }]
+        );
+    }
+
+    #[test]
+    fn overlapping_pieces() {
+        let mut config = BoardConfig::from_fen_str("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        config.bitboards[BoardPiece::WhiteKnight as usize].set(Square::E1);
+        assert_eq!(
+            config.validate(),
+            Err(vec![PositionError::OverlappingPieces(Square::E1)])
+        );
+    }
+
+    #[test]
+    fn pawn_on_back_rank() {
+        assert_eq!(
+            errors("P3k3/8/8/8/8/8/8/4K2p w - - 0 1"),
+            vec![
+                PositionError::PawnOnBackRank(Square::A8),
+                PositionError::PawnOnBackRank(Square::H1)
+            ]
+        );
+    }
+
+    #[test]
+    fn castling_without_rook() {
+        assert_eq!(
+            errors("4k3/8/8/8/8/8/8/4K2R w KQ - 0 1"),
+            vec![PositionError::InvalidCastle {
+                color: Color::White,
+                side: CastleType::QueenSide
+            }]
+        );
+        // The king has left its home rank
+        assert_eq!(
+            errors("r3k2r/8/8/8/8/8/4K3/R6R w KQkq - 0 1"),
+            vec![
+                PositionError::InvalidCastle {
+                    color: Color::White,
+                    side: CastleType::KingSide
+                },
+                PositionError::InvalidCastle {
+                    color: Color::White,
+                    side: CastleType::QueenSide
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn en_passant_without_pawn() {
+        assert_eq!(
+            errors("4k3/8/8/8/8/8/8/4K3 b - e3 0 1"),
+            vec![PositionError::InvalidEnPassant(Square::E3)]
+        );
+        // Only the side to move can capture en passant
+        assert_eq!(
+            errors("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1"),
+            vec![PositionError::InvalidEnPassant(Square::E3)]
+        );
+    }
+
+    #[test]
+    fn opponent_in_check() {
+        assert_eq!(
+            errors("4k3/8/8/8/8/8/8/K3R3 w - - 0 1"),
+            vec![PositionError::OpponentInCheck(Color::Black)]
+        );
+        assert_eq!(errors("4k3/8/8/8/8/8/8/K3R3 b - - 0 1"), vec![]);
+    }
+
+    #[test]
+    fn strict_fen_loader() {
+        assert!(BoardConfig::try_from_fen("8/8/8/8/8/8/8/8 w - - 0 1").is_ok());
+        assert_eq!(
+            BoardConfig::try_from_fen_strict("8/8/8/8/8/8/8/4K3 w - - 0 1").err(),
+            Some(FenError::IllegalPosition(vec![PositionError::KingCount {
+                color: Color::Black,
+                count: 0
+            }]))
+        );
+    }
+}
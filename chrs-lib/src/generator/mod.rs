@@ -1,10 +1,15 @@
 pub mod tables;
 
 use crate::data::{
-    BitBoard, BoardConfig, BoardPiece, Color, Move, MoveList, MoveType, Square, B_PIECES, W_PIECES,
+    BitBoard, BoardConfig, BoardPiece, CastleType, Color, GameState, Move, MoveList, MoveType,
+    Square, B_PIECES, W_PIECES,
 };
+use strum::IntoEnumIterator;
 use tables::*;
 
+/// Piece values used by [`MoveGenerator::see`], indexed by piece kind.
+pub const SEE_VALUES: [i32; 6] = [100, 325, 325, 500, 1000, 20000];
+
 pub struct MoveGenerator {
     rook_magics: [MagicEntry; 64],
     bishop_magics: [MagicEntry; 64],
@@ -52,11 +57,7 @@ impl Default for MoveGenerator {
 
 impl MoveGenerator {
     pub fn update_state(&self, config: &mut BoardConfig) {
-        let sq = match config.get_active_color() {
-            Color::White => config.bitboards[BoardPiece::WhiteKing as usize].peek(),
-            Color::Black => config.bitboards[BoardPiece::BlackKing as usize].peek(),
-        };
-        let is_attacked = self.is_sq_attacked(sq.unwrap(), !config.get_active_color(), config);
+        let is_attacked = config.is_king_in_check(self, config.get_active_color());
         let can_move = self
             .gen_all_moves(config.get_active_color(), config, false)
             .len()
@@ -66,6 +67,22 @@ impl MoveGenerator {
             config.set_mate(config.get_active_color());
         } else if !can_move {
             config.set_stalemate();
+        } else if config.is_insufficient_material() {
+            config.state = GameState::InsufficientMaterial;
+        } else {
+            let repetitions = config.repetition_count();
+            let halfmoves = config.get_halfmove_clock();
+            config.state = if repetitions >= 5 {
+                GameState::FivefoldRepetition
+            } else if halfmoves >= 150 {
+                GameState::SeventyFiveMoveRule
+            } else if repetitions >= 3 {
+                GameState::ThreefoldRepetition
+            } else if halfmoves >= 100 {
+                GameState::FiftyMoveRule
+            } else {
+                GameState::InPlay
+            };
         }
     }
 
@@ -136,44 +153,8 @@ impl MoveGenerator {
                 self.get_rook_moves(pos, blockers, friendly)
                     | self.get_bishop_moves(pos, blockers, friendly)
             }
-            WhiteKing => {
-                let all = config.all_occupancy();
-                let mut moves = self.get_king_atk(pos) & !friendly;
-                if pos == Square::E1 && config.get_can_white_castle_kingside() {
-                    if !(all.is_set(Square::F1) || all.is_set(Square::G1))
-                        && !self.is_sq_attacked(Square::F1, Color::Black, config)
-                    {
-                        moves.set(Square::G1);
-                    }
-                }
-                if pos == Square::E1 && config.get_can_white_castle_queenside() {
-                    if !(all.is_set(Square::B1) || all.is_set(Square::C1) || all.is_set(Square::D1))
-                        && !self.is_sq_attacked(Square::D1, Color::Black, config)
-                    {
-                        moves.set(Square::C1);
-                    }
-                }
-                moves
-            }
-            BlackKing => {
-                let all = config.all_occupancy();
-                let mut moves = self.get_king_atk(pos) & !friendly;
-                if pos == Square::E8 && config.get_can_black_castle_kingside() {
-                    if !(all.is_set(Square::F8) || all.is_set(Square::G8))
-                        && !self.is_sq_attacked(Square::F8, Color::White, config)
-                    {
-                        moves.set(Square::G8);
-                    }
-                }
-                if pos == Square::E8 && config.get_can_black_castle_queenside() {
-                    if !(all.is_set(Square::B8) || all.is_set(Square::C8) || all.is_set(Square::D8))
-                        && !self.is_sq_attacked(Square::D8, Color::White, config)
-                    {
-                        moves.set(Square::C8);
-                    }
-                }
-                moves
-            }
+            WhiteKing => self.get_king_atk(pos) & !friendly,
+            BlackKing => self.get_king_atk(pos) & !friendly,
             WhitePawn => {
                 let quiet = {
                     if pos < Square::A8 {
@@ -225,7 +206,64 @@ impl MoveGenerator {
         if only_captures {
             self.make_movelist((moves & enemy) | ep_moves, pos, config, list)
         } else {
-            self.make_movelist(moves | ep_moves, pos, config, list)
+            self.make_movelist(moves | ep_moves, pos, config, list);
+            if piece == WhiteKing || piece == BlackKing {
+                self.gen_castle_moves(piece, pos, config, list);
+            }
+        }
+    }
+
+    // Castling for standard chess and Chess960 alike. Every square the king
+    // and rook travel over must be empty apart from the two of them, and the
+    // king may not start on, pass or land on an attacked square.
+    fn gen_castle_moves(
+        &self,
+        king: BoardPiece,
+        pos: Square,
+        config: &mut BoardConfig,
+        list: &mut MoveList,
+    ) {
+        let color = king.get_color();
+        for side in [CastleType::KingSide, CastleType::QueenSide] {
+            if !config.can_castle(color, side) {
+                continue;
+            }
+            let rook = config.get_castle_rook(color, side);
+            // Rights read from a FEN that was not validated may have no rook
+            let rook_piece = match color {
+                Color::White => BoardPiece::WhiteRook,
+                Color::Black => BoardPiece::BlackRook,
+            };
+            if config.get_at_sq(rook) != Some(rook_piece) {
+                continue;
+            }
+            let (king_to, rook_to) = side.destinations(color);
+            let king_path = squares_between(pos, king_to);
+            let rook_path = squares_between(rook, rook_to);
+
+            let mut occupied = config.all_occupancy();
+            occupied.unset(pos);
+            occupied.unset(rook);
+            if *(occupied & (king_path | rook_path)) > 0 {
+                continue;
+            }
+
+            let mut attacked = false;
+            let mut path = king_path;
+            while let Some(sq) = path.pop_sq() {
+                if self.is_sq_attacked(sq, !color, config) {
+                    attacked = true;
+                    break;
+                }
+            }
+            if attacked {
+                continue;
+            }
+
+            let m = Move::new(pos, rook, king, None, MoveType::Castle(side));
+            if self.is_legal(m, config, color) {
+                list.push(m);
+            }
         }
     }
 
@@ -296,34 +334,158 @@ impl MoveGenerator {
         }
     }
 
-    fn get_rook_atk(&self, sq: Square, blockers: BitBoard) -> BitBoard {
+    /// Pieces of both colors attacking `sq` with `occupied` as the blockers.
+    /// Only pieces on `occupied` are returned.
+    pub fn attackers_to(&self, sq: Square, occupied: BitBoard, config: &BoardConfig) -> BitBoard {
+        use BoardPiece::*;
+        let bb = |p: BoardPiece| *config.bitboards[p as usize];
+        let diagonal = bb(WhiteBishop) | bb(BlackBishop) | bb(WhiteQueen) | bb(BlackQueen);
+        let straight = bb(WhiteRook) | bb(BlackRook) | bb(WhiteQueen) | bb(BlackQueen);
+        let attackers = (*self.get_black_pawn_atk(sq) & bb(WhitePawn))
+            | (*self.get_white_pawn_atk(sq) & bb(BlackPawn))
+            | (*self.get_knight_atk(sq) & (bb(WhiteKnight) | bb(BlackKnight)))
+            | (*self.get_king_atk(sq) & (bb(WhiteKing) | bb(BlackKing)))
+            | (*self.get_bishop_atk(sq, occupied) & diagonal)
+            | (*self.get_rook_atk(sq, occupied) & straight);
+        BitBoard::from(attackers & *occupied)
+    }
+
+    /// Static exchange evaluation of `m`: the material balance, in
+    /// centipawns for the side making the move, of the capture sequence on
+    /// its target square when both sides always recapture with their least
+    /// valuable piece and may stop whenever it suits them. Sliders behind a
+    /// piece that captured join the exchange. Pins are ignored.
+    pub fn see(&self, config: &BoardConfig, m: Move) -> i32 {
+        if let MoveType::Castle(_) = m.move_type {
+            return 0;
+        }
+        let value = |p: BoardPiece| SEE_VALUES[p as usize % 6];
+
+        let mut occupied = *config.all_occupancy() & !(1 << m.from as usize);
+        let mut gain = [0; 32];
+        gain[0] = m.capture.map_or(0, value);
+        // The piece standing on the target square once the move is made
+        let mut on_target = value(m.p);
+        match m.move_type {
+            MoveType::EnPassant => {
+                // The captured pawn is beside the target, not on it
+                let (file, _): (usize, usize) = m.to.into();
+                let (_, rank): (usize, usize) = m.from.into();
+                occupied &= !(1 << (rank * 8 + file));
+                gain[0] = SEE_VALUES[0];
+            }
+            MoveType::Promotion(Some(prom)) => {
+                gain[0] += value(prom) - SEE_VALUES[0];
+                on_target = value(prom);
+            }
+            _ => {}
+        }
+
+        let diagonal = [
+            BoardPiece::WhiteBishop,
+            BoardPiece::BlackBishop,
+            BoardPiece::WhiteQueen,
+            BoardPiece::BlackQueen,
+        ]
+        .iter()
+        .fold(0, |acc, &p| acc | *config.bitboards[p as usize]);
+        let straight = [
+            BoardPiece::WhiteRook,
+            BoardPiece::BlackRook,
+            BoardPiece::WhiteQueen,
+            BoardPiece::BlackQueen,
+        ]
+        .iter()
+        .fold(0, |acc, &p| acc | *config.bitboards[p as usize]);
+
+        let mut attackers = *self.attackers_to(m.to, occupied.into(), config);
+        let mut side = !m.p.get_color();
+        let mut depth = 0;
+        loop {
+            // Least valuable attacker of the side to recapture
+            let next = BoardPiece::iter()
+                .filter(|p| p.get_color() == side)
+                .map(|p| (p, attackers & *config.bitboards[p as usize]))
+                .find(|(_, bb)| *bb != 0);
+            let (p, bb) = match next {
+                Some(next) => next,
+                None => break,
+            };
+
+            depth += 1;
+            gain[depth] = on_target - gain[depth - 1];
+            if depth == gain.len() - 1 {
+                break;
+            }
+
+            let from = bb & bb.wrapping_neg();
+            occupied &= !from;
+            // Sliders lined up behind the capturing piece
+            attackers |= *self.get_bishop_atk(m.to, occupied.into()) & diagonal;
+            attackers |= *self.get_rook_atk(m.to, occupied.into()) & straight;
+            attackers &= occupied;
+            on_target = value(p);
+            side = !side;
+        }
+
+        // Each side only captures when that beats standing pat
+        while depth > 0 {
+            gain[depth - 1] = -i32::max(-gain[depth - 1], gain[depth]);
+            depth -= 1;
+        }
+        gain[0]
+    }
+
+    /// Squares attacked by a rook on `sq`, the first blocker in each
+    /// direction is included.
+    pub fn get_rook_atk(&self, sq: Square, blockers: BitBoard) -> BitBoard {
         let magic = self.rook_magics[sq as usize];
         // let moves = &self.rook_moves[sq as usize];
         self.rook_moves[magic_index(&magic, blockers)]
     }
 
-    fn get_bishop_atk(&self, sq: Square, blockers: BitBoard) -> BitBoard {
+    /// Squares attacked by a bishop on `sq`, the first blocker in each
+    /// direction is included.
+    pub fn get_bishop_atk(&self, sq: Square, blockers: BitBoard) -> BitBoard {
         let magic = self.bishop_magics[sq as usize];
         // let moves = &self.bishop_moves[sq as usize];
         self.bishop_moves[magic_index(&magic, blockers)]
     }
 
-    fn get_white_pawn_atk(&self, sq: Square) -> BitBoard {
+    pub fn get_queen_atk(&self, sq: Square, blockers: BitBoard) -> BitBoard {
+        self.get_rook_atk(sq, blockers) | self.get_bishop_atk(sq, blockers)
+    }
+
+    pub fn get_white_pawn_atk(&self, sq: Square) -> BitBoard {
         WP_ATK_TBL[sq as usize].into()
     }
 
-    fn get_black_pawn_atk(&self, sq: Square) -> BitBoard {
+    pub fn get_black_pawn_atk(&self, sq: Square) -> BitBoard {
         BP_ATK_TBL[sq as usize].into()
     }
 
-    fn get_knight_atk(&self, sq: Square) -> BitBoard {
+    pub fn get_knight_atk(&self, sq: Square) -> BitBoard {
         N_ATK_TBL[sq as usize].into()
     }
 
-    fn get_king_atk(&self, sq: Square) -> BitBoard {
+    pub fn get_king_atk(&self, sq: Square) -> BitBoard {
         K_ATK_TBL[sq as usize].into()
     }
 
+    /// Squares attacked by `p` standing on `sq`.
+    pub fn get_piece_atk(&self, p: BoardPiece, sq: Square, blockers: BitBoard) -> BitBoard {
+        use BoardPiece::*;
+        match p {
+            WhitePawn => self.get_white_pawn_atk(sq),
+            BlackPawn => self.get_black_pawn_atk(sq),
+            WhiteKnight | BlackKnight => self.get_knight_atk(sq),
+            WhiteBishop | BlackBishop => self.get_bishop_atk(sq, blockers),
+            WhiteRook | BlackRook => self.get_rook_atk(sq, blockers),
+            WhiteQueen | BlackQueen => self.get_queen_atk(sq, blockers),
+            WhiteKing | BlackKing => self.get_king_atk(sq),
+        }
+    }
+
     fn get_rook_moves(&self, sq: Square, blockers: BitBoard, friendly: BitBoard) -> BitBoard {
         self.get_rook_atk(sq, blockers) & !friendly
     }
@@ -371,22 +533,170 @@ impl MoveGenerator {
                     Color::Black => {
                         let m = Move::new_prom(from, to, p, m.capture, BlackRook);
                         if self.is_legal(m, config, p.get_color()) {
+                            list.push(m);
                             list.push(Move::new_prom(from, to, p, m.capture, BlackBishop));
                             list.push(Move::new_prom(from, to, p, m.capture, BlackKnight));
                             list.push(Move::new_prom(from, to, p, m.capture, BlackQueen));
                         }
                     }
                 }
-            } else {
-                if let MoveType::Castle(_) = m.move_type {
-                    if self.is_sq_attacked(from, !p.get_color(), config) {
-                        continue;
-                    }
-                }
-                if self.is_legal(m, config, p.get_color()) {
-                    list.push(m);
-                }
+            } else if self.is_legal(m, config, p.get_color()) {
+                list.push(m);
             }
         }
     }
 }
+
+/// Generator shared by the tests, building the magic tables takes a while.
+#[cfg(test)]
+pub(crate) fn test_generator() -> &'static MoveGenerator {
+    static GEN: std::sync::OnceLock<MoveGenerator> = std::sync::OnceLock::new();
+    GEN.get_or_init(MoveGenerator::default)
+}
+
+// Squares of the rank from `a` to `b`, both included.
+fn squares_between(a: Square, b: Square) -> BitBoard {
+    let (lo, hi) = (
+        usize::min(a as usize, b as usize),
+        usize::max(a as usize, b as usize),
+    );
+    BitBoard::from((u64::MAX >> (63 - hi)) & (u64::MAX << lo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perft(config: &mut BoardConfig, gen: &MoveGenerator, depth: usize) -> usize {
+        let moves = gen.gen_all_moves(config.get_active_color(), config, false);
+        if depth == 1 {
+            return moves.len();
+        }
+        let mut count = 0;
+        for m in moves.iter() {
+            if let Some(commit) = config.make_move(*m) {
+                count += perft(config, gen, depth - 1);
+                config.undo_commit(&commit);
+            }
+        }
+        count
+    }
+
+    // Node counts from depth 1 up to the number of counts given.
+    fn check_perft(fen: &str, counts: &[usize]) {
+        let gen = test_generator();
+        let mut config = BoardConfig::from_fen_str(fen);
+        let before = config.get_fen();
+        for (depth, expected) in counts.iter().enumerate() {
+            assert_eq!(
+                perft(&mut config, gen, depth + 1),
+                *expected,
+                "depth {} of {}",
+                depth + 1,
+                fen
+            );
+        }
+        // Every move was taken back
+        assert_eq!(config.get_fen(), before);
+    }
+
+    #[test]
+    fn perft_standard() {
+        check_perft(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &[20, 400, 8902, 197281],
+        );
+        // Kiwipete
+        check_perft(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2039, 97862],
+        );
+        check_perft(
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2812, 43238],
+        );
+        check_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9467],
+        );
+        check_perft(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1486, 62379],
+        );
+    }
+
+    #[test]
+    fn perft_chess960() {
+        for (fen, counts) in [
+            (
+                "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+                [21, 528, 12189],
+            ),
+            (
+                "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+                [21, 807, 18002],
+            ),
+            (
+                "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+                [20, 479, 10471],
+            ),
+            (
+                "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+                [22, 593, 13440],
+            ),
+            (
+                "1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/QNBBNRKR w HFhf - 0 9",
+                [28, 1120, 31058],
+            ),
+            // The king castles over the square of its own rook
+            ("rkr5/ppp5/8/8/8/8/PPP5/RKR5 w CAca - 0 1", [12, 139, 2266]),
+            (
+                "1r2k1r1/pppppppp/8/8/8/8/PPPPPPPP/1R2K1R1 w GBgb - 0 1",
+                [25, 625, 15131],
+            ),
+        ] {
+            assert!(BoardConfig::from_fen_str(fen).is_chess960(), "{}", fen);
+            check_perft(fen, &counts);
+        }
+    }
+
+    #[test]
+    fn castling_needs_its_rook() {
+        let gen = test_generator();
+        // Not validated, the right is kept although h1 is empty
+        let mut config = BoardConfig::try_from_fen("4k3/8/8/8/8/8/8/4K3 w K - 0 1").unwrap();
+        let moves = gen.gen_all_moves(Color::White, &mut config, false);
+        assert!(moves
+            .iter()
+            .all(|m| !matches!(m.move_type, MoveType::Castle(_))));
+        assert_eq!(config.get_fen(), "4k3/8/8/8/8/8/8/4K3 w K - 0 1");
+    }
+
+    fn see(fen: &str, uci: &str) -> i32 {
+        let gen = test_generator();
+        let config = BoardConfig::from_fen_str(fen);
+        let m = Move::from_uci(uci, &config, gen).unwrap();
+        gen.see(&config, m)
+    }
+
+    #[test]
+    fn static_exchange() {
+        // Queen takes a pawn defended by a pawn
+        assert_eq!(see("4k3/8/3p4/4p3/8/8/8/4QK2 w - - 0 1", "e1e5"), -900);
+        // Knight for knight
+        assert_eq!(see("4k3/8/3p4/4n3/8/5N2/8/4K3 w - - 0 1", "f3e5"), 0);
+        // Nothing defends the pawn
+        assert_eq!(see("4k3/8/8/4p3/8/8/8/4QK2 w - - 0 1", "e1e5"), 100);
+    }
+
+    #[test]
+    fn static_exchange_x_ray() {
+        // The rook on e1 recaptures through the square the first rook left
+        assert_eq!(see("4k3/4r3/8/4p3/8/8/4R3/4RK2 w - - 0 1", "e2e5"), 100);
+        // Without it the rook is lost for a pawn
+        assert_eq!(see("4k3/4r3/8/4p3/8/8/4R3/5K2 w - - 0 1", "e2e5"), -400);
+        // A queen behind a bishop joins on the diagonal
+        assert_eq!(see("4k3/6p1/5n2/8/3B4/2Q5/8/4K3 w - - 0 1", "d4f6"), 100);
+        assert_eq!(see("4k3/6p1/5n2/8/3B4/8/8/4K3 w - - 0 1", "d4f6"), 0);
+    }
+}
@@ -273,7 +273,7 @@ const fn rook_relevant_occupancy(sq: usize) -> u64 {
     b
 }
 
-const fn generate_bishop_attack(sq: usize, block: u64) -> u64 {
+pub const fn generate_bishop_attack(sq: usize, block: u64) -> u64 {
     let sq = sq as i8;
     let mut b = 0;
 
@@ -324,7 +324,7 @@ const fn generate_bishop_attack(sq: usize, block: u64) -> u64 {
     b
 }
 
-const fn generate_rook_attack(sq: usize, block: u64) -> u64 {
+pub const fn generate_rook_attack(sq: usize, block: u64) -> u64 {
     let sq = sq as i8;
     let mut b = 0;
 
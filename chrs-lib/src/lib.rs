@@ -1,6 +1,7 @@
 pub mod ai;
 pub mod data;
 pub mod generator;
+pub mod pgn;
 pub mod zobrist;
 
 mod prng;
@@ -0,0 +1,356 @@
+mod reader;
+
+use std::fmt::Display;
+
+use crate::data::{BoardConfig, Color, FenError, GameState, Move, SanError};
+use crate::generator::MoveGenerator;
+
+pub use reader::read_games;
+
+const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const MAX_LINE_LENGTH: usize = 79;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PgnError {
+    UnterminatedComment,
+    UnterminatedTag,
+    UnbalancedVariation,
+    UnexpectedChar(char),
+    InvalidFen(FenError),
+    InvalidMove { ply: usize, error: SanError },
+}
+
+impl Display for PgnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use PgnError::*;
+        match self {
+            UnterminatedComment => write!(f, "comment is not terminated"),
+            UnterminatedTag => write!(f, "tag pair is not terminated"),
+            UnbalancedVariation => write!(f, "variation parentheses are not balanced"),
+            UnexpectedChar(c) => write!(f, "unexpected character '{}'", c),
+            InvalidFen(e) => write!(f, "invalid FEN tag: {}", e),
+            InvalidMove { ply, error } => write!(f, "move {}: {}", ply / 2 + 1, error),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+/// A move of the movetext together with its annotations. `variations` are
+/// alternatives to this move, each starting from the same position.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PgnMove {
+    pub san: String,
+    pub nags: Vec<u8>,
+    pub comment_before: Option<String>,
+    pub comment: Option<String>,
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+impl PgnMove {
+    pub fn new(san: String) -> Self {
+        Self {
+            san,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<PgnMove>,
+    pub result: String,
+}
+
+impl Default for PgnGame {
+    fn default() -> Self {
+        Self {
+            tags: Vec::new(),
+            moves: Vec::new(),
+            result: "*".to_string(),
+        }
+    }
+}
+
+impl PgnGame {
+    /// Builds a game from the moves played on `config`. The starting position
+    /// is recovered by taking back the whole history, a `FEN` tag is only
+    /// added when it is not the standard one.
+    pub fn from_config(config: &BoardConfig, gen: &MoveGenerator) -> Self {
+        let mut start = config.clone();
+        while !start.move_history.is_empty() {
+            start.undo();
+        }
+
+        let result = result_of(config.get_state());
+        let mut game = PgnGame {
+            tags: Vec::new(),
+            moves: Vec::new(),
+            result: result.to_string(),
+        };
+        // Seven tag roster, in the order the standard requires
+        for (name, value) in [
+            ("Event", "?"),
+            ("Site", "?"),
+            ("Date", "????.??.??"),
+            ("Round", "?"),
+            ("White", "?"),
+            ("Black", "?"),
+            ("Result", result),
+        ] {
+            game.set_tag(name, value);
+        }
+        if config.is_chess960() {
+            game.set_tag("Variant", "Chess960");
+        }
+        let fen = start.get_fen();
+        if fen != START_FEN {
+            game.set_tag("SetUp", "1");
+            game.set_tag("FEN", &fen);
+        }
+
+        for commit in config.move_history.iter() {
+            game.moves.push(PgnMove::new(commit.m.to_san(&start, gen)));
+            start.apply_move(commit.m);
+        }
+        game
+    }
+
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Replaces the value of the tag `name` or appends it if not present.
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Position the movetext starts from, given by the `FEN` tag if present.
+    pub fn starting_position(&self) -> Result<BoardConfig, PgnError> {
+        let mut config = match self.get_tag("FEN") {
+            Some(fen) => BoardConfig::try_from_fen_strict(fen).map_err(PgnError::InvalidFen)?,
+            None => BoardConfig::default(),
+        };
+        if let Some(variant) = self.get_tag("Variant") {
+            let variant = variant.to_lowercase();
+            if variant.contains("960") || variant.contains("fischer") {
+                config.set_chess960(true);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Plays the main line onto the starting position. Variations are only
+    /// kept for display and are not checked.
+    pub fn replay(&self, gen: &MoveGenerator) -> Result<BoardConfig, PgnError> {
+        let mut config = self.starting_position()?;
+        let first_ply = first_ply(&config);
+        for (i, pm) in self.moves.iter().enumerate() {
+            let m =
+                Move::from_san(&pm.san, &config, gen).map_err(|error| PgnError::InvalidMove {
+                    ply: first_ply + i,
+                    error,
+                })?;
+            config.apply_move(m);
+        }
+        gen.update_state(&mut config);
+        Ok(config)
+    }
+
+    fn movetext(&self) -> Vec<String> {
+        let first_ply = self.starting_position().map(|c| first_ply(&c)).unwrap_or(0);
+        let mut tokens = Vec::new();
+        write_line(&self.moves, first_ply, &mut tokens);
+        tokens.push(self.result.clone());
+        tokens
+    }
+}
+
+impl Display for PgnGame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, value) in self.tags.iter() {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(f, "[{} \"{}\"]", name, value)?;
+        }
+        writeln!(f)?;
+
+        let mut line = String::new();
+        for token in self.movetext() {
+            if !line.is_empty() && line.len() + token.len() + 1 > MAX_LINE_LENGTH {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() && !line.ends_with('(') && !token.starts_with(')') {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{}", line)
+    }
+}
+
+/// Game termination marker for a final position.
+pub fn result_of(state: GameState) -> &'static str {
+    match state {
+        GameState::InPlay => "*",
+        GameState::Mate(Color::White) => "0-1",
+        GameState::Mate(Color::Black) => "1-0",
+        _ => "1/2-1/2",
+    }
+}
+
+/// Writes every game separated by a blank line.
+pub fn write_games(games: &[PgnGame]) -> String {
+    games
+        .iter()
+        .map(|g| g.to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Number of half moves played before the position of `config`.
+fn first_ply(config: &BoardConfig) -> usize {
+    let fullmove = usize::max(config.get_fullmove_number() as usize, 1);
+    let black = (config.get_active_color() == Color::Black) as usize;
+    (fullmove - 1) * 2 + black
+}
+
+fn comment_token(comment: &str) -> String {
+    format!("{{{}}}", comment.replace('}', ""))
+}
+
+fn write_line(moves: &[PgnMove], first_ply: usize, tokens: &mut Vec<String>) {
+    // Black moves need their number after anything that interrupts the line
+    let mut interrupted = true;
+    for (i, m) in moves.iter().enumerate() {
+        let ply = first_ply + i;
+        if let Some(c) = &m.comment_before {
+            tokens.push(comment_token(c));
+            interrupted = true;
+        }
+        let white = ply % 2 != 1;
+        if white {
+            tokens.push(format!("{}.", ply / 2 + 1));
+        } else if interrupted {
+            tokens.push(format!("{}...", ply / 2 + 1));
+        }
+        tokens.push(m.san.clone());
+        interrupted = false;
+
+        for nag in m.nags.iter() {
+            tokens.push(format!("${}", nag));
+        }
+        if let Some(c) = &m.comment {
+            tokens.push(comment_token(c));
+            interrupted = true;
+        }
+        for variation in m.variations.iter() {
+            tokens.push("(".to_string());
+            write_line(variation, ply, tokens);
+            tokens.push(")".to_string());
+            interrupted = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::test_generator;
+
+    const OPERA_GAME: &str = "[Event \"Paris\"]
+[White \"Morphy\"]
+[Black \"Duke Karl / Count Isouard\"]
+[Result \"1-0\"]
+
+1. e4 e5 2. Nf3 d6 3. d4 Bg4 4. dxe5 Bxf3 5. Qxf3 dxe5 6. Bc4 Nf6 7. Qb3 Qe7
+8. Nc3 c6 9. Bg5 b5 10. Nxb5 cxb5 11. Bxb5+ Nbd7 12. O-O-O Rd8 13. Rxd7 Rxd7
+14. Rd1 Qe6 15. Bxd7+ Nxd7 16. Qb8+ Nxb8 17. Rd8# 1-0
+";
+
+    #[test]
+    fn write_read_round_trip() {
+        let gen = test_generator();
+        let games = read_games(OPERA_GAME).unwrap();
+        assert_eq!(games.len(), 1);
+        let config = games[0].replay(gen).unwrap();
+        assert_eq!(config.get_state(), GameState::Mate(Color::Black));
+
+        let game = PgnGame::from_config(&config, gen);
+        assert_eq!(game.result, "1-0");
+        assert_eq!(game.get_tag("Result"), Some("1-0"));
+        assert_eq!(game.moves.len(), 33);
+        let text = game.to_string();
+        assert!(text.lines().all(|l| l.len() <= MAX_LINE_LENGTH), "{}", text);
+        assert_eq!(read_games(&text).unwrap(), vec![game.clone()]);
+        assert_eq!(game.replay(gen).unwrap().get_fen(), config.get_fen());
+    }
+
+    #[test]
+    fn write_annotations() {
+        let mut game = read_games("1. e4 e5 2. Nf3 *").unwrap().remove(0);
+        game.moves[0].nags.push(1);
+        game.moves[0].comment = Some("best by test".to_string());
+        let mut sicilian = PgnMove::new("c5".to_string());
+        sicilian.comment_before = Some("Sicilian".to_string());
+        game.moves[1]
+            .variations
+            .push(vec![sicilian, PgnMove::new("Nf3".to_string())]);
+
+        let text = game.to_string();
+        assert!(
+            text.contains("1. e4 $1 {best by test} 1... e5 ({Sicilian} 1... c5 2. Nf3) 2. Nf3 *"),
+            "{}",
+            text
+        );
+        assert_eq!(read_games(&text).unwrap(), vec![game]);
+    }
+
+    #[test]
+    fn starting_position_from_fen_tag() {
+        let gen = test_generator();
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 b - - 0 7";
+        let text = format!("[SetUp \"1\"]\n[FEN \"{}\"]\n\n7... Kd7 8. e4 *", fen);
+        let game = read_games(&text).unwrap().remove(0);
+        let config = game.replay(gen).unwrap();
+        assert_eq!(config.get_fen(), "8/3k4/8/8/4P3/8/8/4K3 b - e3 0 8");
+
+        let written = PgnGame::from_config(&config, gen);
+        assert_eq!(written.get_tag("FEN"), Some(fen));
+        assert!(written.to_string().contains("7... Kd7 8. e4 *"));
+    }
+
+    #[test]
+    fn bad_moves_are_errors() {
+        let gen = test_generator();
+        let game = read_games("1. e4 e5 2. Nf6 *").unwrap().remove(0);
+        assert_eq!(
+            game.replay(gen).err(),
+            Some(PgnError::InvalidMove {
+                ply: 2,
+                error: SanError::Illegal("Nf6".to_string())
+            })
+        );
+
+        let game = read_games("1. e4 Qz9 *").unwrap().remove(0);
+        assert_eq!(
+            game.replay(gen).err(),
+            Some(PgnError::InvalidMove {
+                ply: 1,
+                error: SanError::Invalid("Qz9".to_string())
+            })
+        );
+
+        let game = read_games("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n\n1. e4 *")
+            .unwrap()
+            .remove(0);
+        assert!(matches!(game.replay(gen), Err(PgnError::InvalidFen(_))));
+    }
+}
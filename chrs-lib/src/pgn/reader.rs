@@ -0,0 +1,319 @@
+use std::iter::Peekable;
+use std::mem;
+use std::str::Chars;
+
+use super::{PgnError, PgnGame, PgnMove};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    Open,
+    Close,
+    Symbol(String),
+}
+
+struct Lexer<'a> {
+    chars: Peekable<Chars<'a>>,
+    line_start: bool,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(s: &'a str) -> Self {
+        Self {
+            chars: s.chars().peekable(),
+            line_start: true,
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        self.line_start = c == Some('\n');
+        c
+    }
+
+    fn take_until(&mut self, end: char) -> Option<String> {
+        let mut s = String::new();
+        loop {
+            match self.bump()? {
+                c if c == end => return Some(s),
+                c => s.push(c),
+            }
+        }
+    }
+
+    fn read_tag(&mut self) -> Result<Token, PgnError> {
+        let mut name = String::new();
+        while let Some(c) = self.chars.peek() {
+            if c.is_whitespace() || *c == '"' || *c == ']' {
+                break;
+            }
+            name.push(*c);
+            self.bump();
+        }
+
+        let mut value = String::new();
+        let mut quoted = false;
+        loop {
+            match self.bump().ok_or(PgnError::UnterminatedTag)? {
+                '"' => quoted = !quoted,
+                '\\' if quoted => value.push(self.bump().ok_or(PgnError::UnterminatedTag)?),
+                ']' if !quoted => return Ok(Token::Tag(name, value)),
+                c if quoted => value.push(c),
+                _ => {}
+            }
+        }
+    }
+
+    fn read_symbol(&mut self, first: char) -> String {
+        let mut s = first.to_string();
+        while let Some(c) = self.chars.peek() {
+            if !(c.is_alphanumeric() || "_+#=:-/".contains(*c)) {
+                break;
+            }
+            s.push(*c);
+            self.bump();
+        }
+        s
+    }
+
+    fn read_suffix(&mut self, first: char) -> Token {
+        let mut s = first.to_string();
+        while let Some(c @ ('!' | '?')) = self.chars.peek() {
+            s.push(*c);
+            self.bump();
+        }
+        let nag = match s.as_str() {
+            "!" => 1,
+            "?" => 2,
+            "!!" => 3,
+            "??" => 4,
+            "!?" => 5,
+            "?!" => 6,
+            _ => 0,
+        };
+        Token::Nag(nag)
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token, PgnError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let line_start = self.line_start;
+            let c = self.bump()?;
+            let token = match c {
+                // Escape mechanism, the whole line is meant for other software
+                '%' if line_start => {
+                    self.take_until('\n');
+                    continue;
+                }
+                c if c.is_whitespace() || c == '.' => continue,
+                ';' => {
+                    let s = self.take_until('\n').unwrap_or_default();
+                    self.line_start = true;
+                    Ok(Token::Comment(s.trim().to_string()))
+                }
+                '{' => self
+                    .take_until('}')
+                    .map(|s| Token::Comment(s.split_whitespace().collect::<Vec<_>>().join(" ")))
+                    .ok_or(PgnError::UnterminatedComment),
+                '[' => self.read_tag(),
+                '(' => Ok(Token::Open),
+                ')' => Ok(Token::Close),
+                '$' => {
+                    let mut n = String::new();
+                    while let Some(c) = self.chars.peek().filter(|c| c.is_ascii_digit()) {
+                        n.push(*c);
+                        self.bump();
+                    }
+                    Ok(Token::Nag(n.parse().unwrap_or(0)))
+                }
+                '!' | '?' => Ok(self.read_suffix(c)),
+                '*' => Ok(Token::Symbol("*".to_string())),
+                c if c.is_alphanumeric() => Ok(Token::Symbol(self.read_symbol(c))),
+                c => Err(PgnError::UnexpectedChar(c)),
+            };
+            return Some(token);
+        }
+    }
+}
+
+fn is_result(s: &str) -> bool {
+    matches!(s, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+// Builds the move tree of a single game while its tokens are read.
+#[derive(Default)]
+struct GameBuilder {
+    game: PgnGame,
+    // Lines enclosing the current variation
+    parents: Vec<Vec<PgnMove>>,
+    line: Vec<PgnMove>,
+    comment_before: Option<String>,
+}
+
+impl GameBuilder {
+    fn is_empty(&self) -> bool {
+        self.game.tags.is_empty() && self.line.is_empty() && self.parents.is_empty()
+    }
+
+    fn comment(&mut self, comment: String) {
+        let target = match self.line.last_mut() {
+            Some(m) => &mut m.comment,
+            None => &mut self.comment_before,
+        };
+        match target {
+            Some(c) => {
+                c.push(' ');
+                c.push_str(&comment);
+            }
+            None => *target = Some(comment),
+        }
+    }
+
+    fn finish(&mut self, result: String) -> Result<PgnGame, PgnError> {
+        if !self.parents.is_empty() {
+            return Err(PgnError::UnbalancedVariation);
+        }
+        let mut builder = mem::take(self);
+        builder.game.moves = builder.line;
+        builder.game.result = result;
+        Ok(builder.game)
+    }
+}
+
+/// Parses every game of a PGN file. Games missing a termination marker
+/// end at the next tag section and are given the result `*`.
+pub fn read_games(s: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let mut games = Vec::new();
+    let mut builder = GameBuilder::default();
+
+    for token in Lexer::new(s) {
+        match token? {
+            Token::Tag(name, value) => {
+                if !builder.line.is_empty() {
+                    games.push(builder.finish("*".to_string())?);
+                }
+                builder.game.tags.push((name, value));
+            }
+            Token::Comment(c) => builder.comment(c),
+            Token::Nag(nag) => {
+                if let Some(m) = builder.line.last_mut() {
+                    m.nags.push(nag);
+                }
+            }
+            Token::Open => {
+                if builder.line.is_empty() {
+                    return Err(PgnError::UnbalancedVariation);
+                }
+                let line = mem::take(&mut builder.line);
+                builder.parents.push(line);
+            }
+            Token::Close => {
+                let variation = mem::take(&mut builder.line);
+                builder.line = builder.parents.pop().ok_or(PgnError::UnbalancedVariation)?;
+                builder.comment_before = None;
+                if !variation.is_empty() {
+                    builder.line.last_mut().unwrap().variations.push(variation);
+                }
+            }
+            Token::Symbol(s) if is_result(&s) => games.push(builder.finish(s)?),
+            // Move number indications
+            Token::Symbol(s) if s.chars().all(|c| c.is_ascii_digit()) => {}
+            Token::Symbol(s) => {
+                let mut m = PgnMove::new(s);
+                m.comment_before = builder.comment_before.take();
+                builder.line.push(m);
+            }
+        }
+    }
+
+    if !builder.is_empty() {
+        games.push(builder.finish("*".to_string())?);
+    }
+    Ok(games)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sans(line: &[PgnMove]) -> Vec<&str> {
+        line.iter().map(|m| m.san.as_str()).collect()
+    }
+
+    #[test]
+    fn nested_variations_and_comments() {
+        let text = "[Event \"Test\"]
+
+{Opening} 1. e4 {best by test} e5 (1... c5 {Sicilian} 2. Nf3 (2. c3 d5) 2... d6)
+(1... e6) 2. Nf3!? $14 Nc6 ; rest of line
+*";
+        let games = read_games(text).unwrap();
+        assert_eq!(games.len(), 1);
+        let game = &games[0];
+        assert_eq!(game.tags, vec![("Event".to_string(), "Test".to_string())]);
+        assert_eq!(game.result, "*");
+        assert_eq!(sans(&game.moves), ["e4", "e5", "Nf3", "Nc6"]);
+
+        let e4 = &game.moves[0];
+        assert_eq!(e4.comment_before.as_deref(), Some("Opening"));
+        assert_eq!(e4.comment.as_deref(), Some("best by test"));
+
+        let e5 = &game.moves[1];
+        assert_eq!(e5.variations.len(), 2);
+        let sicilian = &e5.variations[0];
+        assert_eq!(sans(sicilian), ["c5", "Nf3", "d6"]);
+        assert_eq!(sicilian[0].comment.as_deref(), Some("Sicilian"));
+        assert_eq!(sans(&sicilian[1].variations[0]), ["c3", "d5"]);
+        assert_eq!(sans(&e5.variations[1]), ["e6"]);
+
+        assert_eq!(game.moves[2].nags, [5, 14]);
+        assert_eq!(game.moves[3].comment.as_deref(), Some("rest of line"));
+    }
+
+    #[test]
+    fn several_games() {
+        let text = "[Event \"A\"]
+1. e4 e5 1-0
+
+[Event \"B\"]
+1. d4 d5
+[Event \"C\"]
+% escaped line 1. c4
+1. c4 1/2-1/2
+";
+        let games = read_games(text).unwrap();
+        let results: Vec<&str> = games.iter().map(|g| g.result.as_str()).collect();
+        assert_eq!(results, ["1-0", "*", "1/2-1/2"]);
+        assert_eq!(sans(&games[1].moves), ["d4", "d5"]);
+        assert_eq!(games[2].get_tag("Event"), Some("C"));
+        assert_eq!(sans(&games[2].moves), ["c4"]);
+        assert_eq!(read_games("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn escaped_tag_values() {
+        let games = read_games("[White \"Doe, \\\"J\\\" \\\\ Co\"]\n*").unwrap();
+        assert_eq!(games[0].get_tag("White"), Some("Doe, \"J\" \\ Co"));
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert_eq!(
+            read_games("1. e4 {open"),
+            Err(PgnError::UnterminatedComment)
+        );
+        assert_eq!(read_games("[Event \"A\""), Err(PgnError::UnterminatedTag));
+        assert_eq!(
+            read_games("1. e4 (1. d4"),
+            Err(PgnError::UnbalancedVariation)
+        );
+        assert_eq!(read_games("1. e4 )"), Err(PgnError::UnbalancedVariation));
+        assert_eq!(read_games("(1. e4) *"), Err(PgnError::UnbalancedVariation));
+        assert_eq!(read_games("1. e4 & *"), Err(PgnError::UnexpectedChar('&')));
+    }
+}
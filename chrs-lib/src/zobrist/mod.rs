@@ -54,6 +54,18 @@ pub fn hash(config: &BoardConfig) -> u64 {
     key
 }
 
+/// Key of the pawns alone, used by the pawn structure evaluation.
+pub fn pawn_hash(config: &BoardConfig) -> u64 {
+    let mut key: u64 = 0;
+    for p in [BoardPiece::WhitePawn, BoardPiece::BlackPawn] {
+        let mut bb = config.bitboards[p as usize];
+        while let Some(sq) = bb.pop_sq() {
+            key ^= PIECE_KEYS[sq as usize][p as usize];
+        }
+    }
+    key
+}
+
 pub fn update_piece(sq: Square, p: BoardPiece, key: &mut u64) {
     *key ^= PIECE_KEYS[sq as usize][p as usize];
 }
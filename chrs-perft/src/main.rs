@@ -1,10 +1,9 @@
 #![allow(warnings, unused)]
 
-use chrs_lib::data::{BoardConfig, BoardPiece, Color, Move, Square};
+use chrs_lib::data::{BoardConfig, Move};
 use chrs_lib::generator::MoveGenerator;
-use chrs_lib::zobrist::hash;
+use chrs_lib::zobrist::{hash, pawn_hash};
 use std::env;
-use std::str::FromStr;
 use std::time::Instant;
 
 fn perft_impl(depth: usize, config: &mut BoardConfig, gen: &MoveGenerator, divide: bool) -> usize {
@@ -25,12 +24,17 @@ fn perft_impl(depth: usize, config: &mut BoardConfig, gen: &MoveGenerator, divid
         if let Some(commit) = config.make_move(*m) {
             let c = perft_impl(depth - 1, config, gen, false);
             if divide {
-                println!("{} {}", commit.m.to_string().to_lowercase(), c);
+                if config.is_chess960() {
+                    println!("{} {}", commit.m.to_uci_chess960(), c);
+                } else {
+                    println!("{} {}", commit.m.to_uci(), c);
+                }
             }
             count += c;
             config.undo_commit(&commit);
             let key_updated = config.get_hash();
             assert_eq!(key_scratch, key_updated);
+            assert_eq!(pawn_hash(config), config.get_pawn_hash());
         }
     }
 
@@ -51,21 +55,7 @@ fn main() {
 
     if moves != "" {
         for i in moves.split(' ').collect::<Vec<&str>>() {
-            let chars = i.chars();
-            let from: Square =
-                Square::from_str(&chars.clone().take(2).collect::<String>()).unwrap();
-            let to: Square =
-                Square::from_str(&chars.clone().skip(2).take(2).collect::<String>()).unwrap();
-            let mut m = Move::infer(from, to, &config);
-            if m.is_empty_prom() {
-                let mut p = format!("{}", chars.clone().last().unwrap());
-                if config.get_active_color() == Color::White {
-                    p = p.to_uppercase();
-                }
-                let prom = BoardPiece::from_str(&p).unwrap();
-                m.set_prom(prom);
-            }
-
+            let m = Move::from_uci(i, &config, &gen).unwrap_or_else(|e| panic!("{}", e));
             config.make_move(m);
         }
     }
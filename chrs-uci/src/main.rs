@@ -0,0 +1,328 @@
+use chrs_lib::ai::{
+    evaluate_traced, AIStat, NegaMaxAI, SearchInfo, SearchLimits, AI, DEFAULT_TT_SIZE_MB,
+    MAX_TT_SIZE_MB,
+};
+use chrs_lib::data::{BoardConfig, Move};
+use chrs_lib::generator::MoveGenerator;
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const NAME: &str = "chess-rs";
+const AUTHOR: &str = "Parth Pant";
+
+const DEFAULT_DEPTH: usize = 5;
+const DEFAULT_QUIESCENCE_DEPTH: usize = 4;
+const DEFAULT_CONTEMPT: i32 = 0;
+const MAX_MULTI_PV: usize = 32;
+
+fn parse_go(args: &[&str]) -> SearchLimits {
+    let mut limits = SearchLimits::default();
+    let mut it = args.iter();
+    while let Some(token) = it.next() {
+        let mut value = || it.next().and_then(|v| v.parse::<u64>().ok());
+        match *token {
+            "depth" => limits.depth = value().map(|d| d as usize),
+            "nodes" => limits.nodes = value().map(|n| n as usize),
+            "movetime" => limits.movetime = value().map(Duration::from_millis),
+            "wtime" => limits.wtime = value().map(Duration::from_millis),
+            "btime" => limits.btime = value().map(Duration::from_millis),
+            "winc" => limits.winc = value().map(Duration::from_millis),
+            "binc" => limits.binc = value().map(Duration::from_millis),
+            "movestogo" => limits.moves_to_go = value().map(|n| n as u32),
+            "infinite" => limits.infinite = true,
+            _ => {}
+        }
+    }
+    limits
+}
+
+struct Engine {
+    config: BoardConfig,
+    gen: Arc<MoveGenerator>,
+    ai: Option<NegaMaxAI>,
+    search: Option<JoinHandle<NegaMaxAI>>,
+    stop: Arc<AtomicBool>,
+    chess960: bool,
+}
+
+impl Engine {
+    fn new() -> Self {
+        let mut ai = NegaMaxAI::new(DEFAULT_DEPTH, DEFAULT_QUIESCENCE_DEPTH);
+        ai.set_info_callback(Box::new(|info| print_info(info, false)));
+        let stop = ai.stop_handle();
+        Self {
+            config: BoardConfig::default(),
+            gen: Arc::new(MoveGenerator::default()),
+            ai: Some(ai),
+            search: None,
+            stop,
+            chess960: false,
+        }
+    }
+
+    fn uci(&self) {
+        println!("id name {}", NAME);
+        println!("id author {}", AUTHOR);
+        println!(
+            "option name Depth type spin default {} min 1 max 32",
+            DEFAULT_DEPTH
+        );
+        println!(
+            "option name QuiescenceDepth type spin default {} min 0 max 16",
+            DEFAULT_QUIESCENCE_DEPTH
+        );
+        println!(
+            "option name Contempt type spin default {} min -1000 max 1000",
+            DEFAULT_CONTEMPT
+        );
+        // The table is a power of two buckets, it takes the largest that fits
+        // in the size asked for. The size used is reported when set.
+        println!(
+            "option name Hash type spin default {} min 1 max {}",
+            DEFAULT_TT_SIZE_MB, MAX_TT_SIZE_MB
+        );
+        println!(
+            "option name MultiPV type spin default 1 min 1 max {}",
+            MAX_MULTI_PV
+        );
+        println!("option name PVS type check default true");
+        println!("option name AspirationWindows type check default true");
+        println!("option name NullMove type check default true");
+        println!("option name LMR type check default true");
+        println!("option name CheckExtensions type check default true");
+        println!("option name ReverseFutility type check default true");
+        println!("option name Futility type check default true");
+        println!("option name DeltaPruning type check default true");
+        println!("option name SEEPruning type check default true");
+        println!("option name QuiescenceEvasions type check default true");
+        println!("option name UCI_Chess960 type check default false");
+        println!("uciok");
+    }
+
+    fn new_game(&mut self) {
+        self.stop();
+        self.config = BoardConfig::default();
+        if let Some(ai) = self.ai.as_mut() {
+            ai.clear();
+        }
+    }
+
+    fn set_option(&mut self, args: &[&str]) {
+        self.stop();
+        let name_at = args.iter().position(|t| *t == "name");
+        let value_at = args.iter().position(|t| *t == "value");
+        let name = match (name_at, value_at) {
+            (Some(n), Some(v)) if n < v => args[n + 1..v].join(" "),
+            (Some(n), None) => args[n + 1..].join(" "),
+            _ => return,
+        };
+        let value = value_at
+            .map(|v| args[v + 1..].join(" "))
+            .unwrap_or_default();
+
+        let ai = self.ai.as_mut().unwrap();
+        match name.to_lowercase().as_str() {
+            "depth" => {
+                if let Ok(d) = value.parse::<usize>() {
+                    ai.depth = d.clamp(1, 32);
+                }
+            }
+            "quiescencedepth" => {
+                if let Ok(d) = value.parse::<usize>() {
+                    ai.quiescence_depth = d.min(16);
+                }
+            }
+            "hash" => {
+                if let Ok(mb) = value.parse::<usize>() {
+                    ai.set_hash_size(mb.clamp(1, MAX_TT_SIZE_MB));
+                    println!("info string hash table of {} KB", ai.hash_size_kb());
+                }
+            }
+            "multipv" => {
+                if let Ok(n) = value.parse::<usize>() {
+                    ai.multi_pv = n.clamp(1, MAX_MULTI_PV);
+                }
+            }
+            "pvs" => ai.features.pvs = value == "true",
+            "aspirationwindows" => ai.features.aspiration = value == "true",
+            "nullmove" => ai.features.null_move = value == "true",
+            "lmr" => ai.features.lmr = value == "true",
+            "checkextensions" => ai.features.check_extensions = value == "true",
+            "reversefutility" => ai.features.reverse_futility = value == "true",
+            "futility" => ai.features.futility = value == "true",
+            "deltapruning" => ai.features.delta_pruning = value == "true",
+            "seepruning" => ai.features.see_pruning = value == "true",
+            "quiescenceevasions" => ai.features.quiescence_evasions = value == "true",
+            "uci_chess960" => {
+                self.chess960 = value == "true";
+                let chess960 = self.chess960;
+                ai.set_info_callback(Box::new(move |info| print_info(info, chess960)));
+            }
+            "contempt" => {
+                if let Ok(c) = value.parse::<i32>() {
+                    ai.contempt = c.clamp(-1000, 1000);
+                }
+            }
+            _ => println!("info string unknown option {}", name),
+        }
+    }
+
+    fn position(&mut self, args: &[&str]) {
+        self.stop();
+        let moves_at = args
+            .iter()
+            .position(|t| *t == "moves")
+            .unwrap_or(args.len());
+        let mut config = match args.first() {
+            Some(&"startpos") => BoardConfig::default(),
+            Some(&"fen") => match BoardConfig::try_from_fen_strict(&args[1..moves_at].join(" ")) {
+                Ok(config) => config,
+                Err(e) => {
+                    println!("info string invalid fen: {}", e);
+                    return;
+                }
+            },
+            _ => return,
+        };
+        if self.chess960 {
+            config.set_chess960(true);
+        }
+
+        for token in args.iter().skip(moves_at + 1) {
+            match Move::from_uci(token, &config, &self.gen) {
+                Ok(m) => config.apply_move(m),
+                Err(e) => {
+                    println!("info string {}", e);
+                    break;
+                }
+            }
+        }
+        self.config = config;
+    }
+
+    fn go(&mut self, limits: SearchLimits) {
+        self.stop();
+        self.stop.store(false, Ordering::Relaxed);
+
+        let mut ai = self.ai.take().unwrap();
+        ai.limits = limits;
+
+        let config = self.config.clone();
+        let gen = Arc::clone(&self.gen);
+        let stop = Arc::clone(&self.stop);
+        let chess960 = self.chess960;
+        self.search = Some(thread::spawn(move || {
+            let best = ai.get_best_move(&config, &gen);
+            print_stats(&ai.get_stats());
+            // In infinite mode the best move may only be sent after `stop`.
+            while limits.infinite && !stop.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(5));
+            }
+            match best {
+                Some(m) => println!("bestmove {}", uci_move(&m, chess960)),
+                None => println!("bestmove 0000"),
+            }
+            ai
+        }));
+    }
+
+    fn stop(&mut self) {
+        if let Some(search) = self.search.take() {
+            self.stop.store(true, Ordering::Relaxed);
+            self.ai = Some(search.join().expect("search thread panicked"));
+        }
+    }
+
+    /// Non standard command printing the evaluation of the current position
+    /// term by term.
+    fn eval(&self) {
+        println!("{}", evaluate_traced(&self.config, &self.gen));
+    }
+}
+
+fn uci_move(m: &Move, chess960: bool) -> String {
+    if chess960 {
+        m.to_uci_chess960()
+    } else {
+        m.to_uci()
+    }
+}
+
+fn print_info(info: &SearchInfo, chess960: bool) {
+    let millis = info.time.as_millis();
+    let nps = (info.nodes as u128 * 1000).checked_div(millis).unwrap_or(0);
+    // One line per pv, numbered only when there are several
+    for (i, line) in info.lines.iter().enumerate() {
+        let multipv = if info.lines.len() > 1 {
+            format!(" multipv {}", i + 1)
+        } else {
+            String::new()
+        };
+        let score = match line.mate_in() {
+            Some(n) => format!("mate {}", n),
+            None => format!("cp {}", line.score),
+        };
+        let pv = line
+            .pv
+            .iter()
+            .map(|m| uci_move(m, chess960))
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!(
+            "info depth {}{} score {} nodes {} nps {} hashfull {} time {} pv {}",
+            info.depth, multipv, score, info.nodes, nps, info.hashfull, millis, pv
+        );
+    }
+}
+
+fn print_stats(stats: &AIStat) {
+    println!(
+        "info string nodes {} qnodes {} pvs_researches {} aspiration_researches {} \
+         null_move_cutoffs {} lmr_researches {} reverse_futility_cutoffs {} \
+         futility_pruned {} delta_pruned {} see_pruned {}",
+        stats.node_count,
+        stats.qnode_count,
+        stats.pvs_researches,
+        stats.aspiration_researches,
+        stats.null_move_cutoffs,
+        stats.lmr_researches,
+        stats.reverse_futility_cutoffs,
+        stats.futility_pruned,
+        stats.delta_pruned,
+        stats.see_pruned
+    );
+}
+
+fn main() {
+    let mut engine = Engine::new();
+
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let (cmd, args) = match tokens.split_first() {
+            Some((cmd, args)) => (*cmd, args),
+            None => continue,
+        };
+
+        match cmd {
+            "uci" => engine.uci(),
+            "isready" => println!("readyok"),
+            "ucinewgame" => engine.new_game(),
+            "setoption" => engine.set_option(args),
+            "position" => engine.position(args),
+            "go" => engine.go(parse_go(args)),
+            "stop" => engine.stop(),
+            "eval" => engine.eval(),
+            "quit" => break,
+            _ => {}
+        }
+    }
+
+    engine.stop();
+}
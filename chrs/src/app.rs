@@ -1,7 +1,8 @@
 use crate::board::{events::BoardEvent, Board};
+use crate::game::GameSetup;
 use crate::ui::GuiFramework;
-use chrs_lib::ai::{NegaMaxAI, AI};
-use chrs_lib::data::{BoardConfig, Color, GameState, MoveList, Square};
+use crate::worker::AIWorker;
+use chrs_lib::data::{BoardConfig, GameState, MoveList, Square};
 use chrs_lib::generator::MoveGenerator;
 
 use log;
@@ -81,7 +82,8 @@ impl App {
         //     "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -",
         // );
         let generator = MoveGenerator::default();
-        let mut ai = NegaMaxAI::default();
+        let mut worker = AIWorker::new();
+        let mut setup = GameSetup::default();
 
         let (mut pixels, mut framework) = {
             let window_size = window.inner_size();
@@ -150,17 +152,24 @@ impl App {
                     }
                 }
                 Event::MainEventsCleared => {
+                    board.set_flipped(setup.is_board_flipped());
+                    if let Some(ai_move) = worker.poll(&generator) {
+                        config.apply_move(ai_move);
+                    }
                     if config.get_state() == GameState::InPlay {
-                        let turn = config.get_active_color();
-                        if turn == Color::Black {
-                            let ai_move = ai.get_best_move(&config, &generator);
-                            if let Some(ai_move) = ai_move {
-                                log::info!("AI response {:?}", ai.get_stats());
-                                config.apply_move(ai_move);
-                            } else {
-                                log::info!("AI did not generate any move");
+                        let player = *setup.player(config.get_active_color());
+                        if !player.is_human() {
+                            board.clear_user_move();
+                            // Not while an earlier position is looked at
+                            let browsing = config.can_redo();
+                            if !worker.is_thinking() && !browsing && setup.engine_may_move() {
+                                worker.search(&config, player.settings);
                             }
                         } else {
+                            // The side was handed over to a human mid search.
+                            if worker.is_thinking() {
+                                worker.cancel();
+                            }
                             if let Some(user_move) = board.get_user_move() {
                                 if moves.as_ref().unwrap().has_target_sq(user_move.to) {
                                     if !user_move.is_empty_prom() {
@@ -187,7 +196,7 @@ impl App {
                     // Redraw here
                     board.draw(pixels.frame_mut(), &generator, &config, &moves);
                     // Prepare egui
-                    framework.prepare(&window, &mut config, &mut ai);
+                    framework.prepare(&window, &mut config, &generator, &mut worker, &mut setup);
                     // Render everything together
                     let render_result = pixels.render_with(|encoder, render_target, context| {
                         // Render the board texture
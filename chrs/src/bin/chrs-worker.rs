@@ -0,0 +1,61 @@
+/* Entry point of the Web Worker the web app searches in, natively the AI
+ * runs on a thread of the app instead. Requests and responses are the text
+ * messages of `worker::protocol`.
+ */
+#[cfg(target_arch = "wasm32")]
+#[path = "../worker/protocol.rs"]
+mod protocol;
+
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    use chrs_lib::ai::{NegaMaxAI, AI};
+    use chrs_lib::generator::MoveGenerator;
+    use protocol::{SearchRequest, WorkerResponse};
+    use wasm_bindgen::closure::Closure;
+    use wasm_bindgen::{JsCast, JsValue};
+    use web_sys::{DedicatedWorkerGlobalScope, MessageEvent};
+
+    fn post(msg: &str) {
+        let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+        scope
+            .post_message(&JsValue::from_str(msg))
+            .expect("failed to message the app");
+    }
+
+    std::panic::set_hook(Box::new(console_error_panic_hook::hook));
+    console_log::init_with_level(log::Level::Info).expect("error initializing logger");
+
+    let gen = MoveGenerator::default();
+    let mut ai = NegaMaxAI::default();
+    let on_message = Closure::wrap(Box::new(move |e: MessageEvent| {
+        let request = e
+            .data()
+            .as_string()
+            .and_then(|msg| SearchRequest::decode(&msg, &gen));
+        let request = match request {
+            Some(request) => request,
+            None => {
+                log::error!("invalid search request: {:?}", e.data());
+                return;
+            }
+        };
+
+        let id = request.id;
+        ai.set_info_callback(Box::new(move |info| {
+            post(&WorkerResponse::Info(id, info.clone()).encode());
+        }));
+        request.settings.apply(&mut ai);
+        let m = ai.get_best_move(&request.config, &gen);
+        post(&WorkerResponse::BestMove(id, m, ai.get_stats()).encode());
+    }) as Box<dyn FnMut(MessageEvent)>);
+
+    let scope: DedicatedWorkerGlobalScope = js_sys::global().unchecked_into();
+    scope.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    on_message.forget();
+    post("ready");
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    eprintln!("chrs-worker is only used by the web build, run chrs instead.");
+}
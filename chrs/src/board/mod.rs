@@ -41,6 +41,7 @@ pub struct Board {
     mouse_state: MouseState,
     user_move: Option<Move>,
     overlay_xywh: (f32, f32, f32, f32),
+    flipped: bool,
 }
 
 impl Default for Board {
@@ -65,6 +66,7 @@ impl Default for Board {
             picked_piece: None,
             user_move: None,
             overlay_xywh: (size/2.0-2.0*check_side, size/2.0-0.5*check_side, 4.0*check_side, check_side),
+            flipped: false,
         }
     }
 }
@@ -82,14 +84,45 @@ impl Board {
         self.picked_piece
     }
 
-    fn get_pos_prom_box(&self, pos: &(usize, usize)) -> Option<(usize, usize)> {
-        let inside = pos.0 > self.overlay_xywh.0 as usize
-            && pos.0 < (self.overlay_xywh.0 + self.overlay_xywh.2) as usize
-            && pos.1 > self.overlay_xywh.1 as usize
-            && pos.1 < (self.overlay_xywh.1 + self.overlay_xywh.3) as usize;
+    pub fn is_flipped(&self) -> bool {
+        self.flipped
+    }
+
+    /// Draw the board with Black at the bottom.
+    pub fn set_flipped(&mut self, flipped: bool) {
+        self.flipped = flipped;
+    }
+
+    /// Maps a square's file and rank to the column and row (from the top
+    /// left) it is drawn at. The mapping is its own inverse.
+    fn orient(&self, (x, y): (usize, usize)) -> (usize, usize) {
+        if self.flipped {
+            (7 - x, y)
+        } else {
+            (x, 7 - y)
+        }
+    }
+
+    /// The promotion choices are shown over the promotion square.
+    fn get_prom_xywh(&self, m: &Move) -> (f32, f32, f32, f32) {
+        let check_side = self.get_check_side();
+        let (w, h) = (4.0 * check_side, check_side);
+        let (col, row) = self.orient(m.to.into());
+        let off = self.ruler_offset as f32;
+        let x = (off + (col as f32 + 0.5) * check_side - w / 2.0)
+            .clamp(off, off + self.side_length as f32 - w);
+        (x, row as f32 * check_side, w, h)
+    }
+
+    fn get_pos_prom_box(&self, m: &Move, pos: &(usize, usize)) -> Option<(usize, usize)> {
+        let xywh = self.get_prom_xywh(m);
+        let inside = pos.0 > xywh.0 as usize
+            && pos.0 < (xywh.0 + xywh.2) as usize
+            && pos.1 > xywh.1 as usize
+            && pos.1 < (xywh.1 + xywh.3) as usize;
         if inside {
-            let x = pos.0 - self.overlay_xywh.0 as usize;
-            let y = pos.1 - self.overlay_xywh.1 as usize;
+            let x = pos.0 - xywh.0 as usize;
+            let y = pos.1 - xywh.1 as usize;
             return Some((x, y));
         }
         None
@@ -106,8 +139,8 @@ impl Board {
             if m.is_empty_prom() {
                 if self.mouse_state.get_is_left_pressed() {
                     let pos = self.mouse_state.get_pos();
-                    if let Some((x, _)) = self.get_pos_prom_box(&pos) {
-                        let i = x / self.overlay_xywh.3 as usize;
+                    if let Some((x, _)) = self.get_pos_prom_box(m, &pos) {
+                        let i = x / self.get_check_side() as usize;
                         let prom = match config.get_active_color() {
                             Color::White => W_PROM_OPTS[i],
                             Color::Black => B_PROM_OPTS[i],
@@ -211,6 +244,7 @@ impl Board {
 
         for i in 0..8 {
             let stroke = tiny_skia::Stroke::default();
+            let (file, rank) = self.orient((i, i));
             {
                 // Y-axis
                 let t1 =
@@ -221,7 +255,7 @@ impl Board {
                     self.ruler_offset as f32 * 0.2,
                     i as f32 * check_side as f32 + check_side * 0.45,
                 );
-                self.draw_char(('1' as u8 + (7 - i)) as char, 20.0, t2, &mut pixmap);
+                self.draw_char(('1' as u8 + rank as u8) as char, 20.0, t2, &mut pixmap);
             }
             {
                 // X-axis
@@ -235,7 +269,7 @@ impl Board {
                     self.ruler_offset as f32 + i as f32 * check_side as f32 + check_side * 0.45,
                     self.side_length as f32 + self.ruler_offset as f32 * 0.2,
                 );
-                self.draw_char(('A' as u8 + i) as char, 17.0, t2, &mut pixmap);
+                self.draw_char(('A' as u8 + file as u8) as char, 17.0, t2, &mut pixmap);
             }
         }
 
@@ -257,9 +291,10 @@ impl Board {
                     }
                 };
 
+                let (col, row) = self.orient((x, y));
                 let t = tiny_skia::Transform::from_translate(
-                    x as f32 * check_side + self.ruler_offset as f32,
-                    (7 - y) as f32 * check_side,
+                    col as f32 * check_side + self.ruler_offset as f32,
+                    row as f32 * check_side,
                 );
                 pixmap.fill_rect(rect, paint, t, None);
                 if let Some(_) = self.picked_piece {
@@ -288,8 +323,8 @@ impl Board {
                     let tree = self.get_glyph_tree(&p);
                     let transform = tiny_skia::Transform::from_translate(
                         // TODO: Fix magic number
-                        x as f32 * check_side + self.ruler_offset as f32 + check_side / 8.0,
-                        (7 - y) as f32 * check_side + check_side / 8.0,
+                        col as f32 * check_side + self.ruler_offset as f32 + check_side / 8.0,
+                        row as f32 * check_side + check_side / 8.0,
                     );
                     let fit = usvg::FitTo::Width(glyph_width);
                     resvg::render(&tree, fit, transform, pixmap.as_mut());
@@ -299,8 +334,8 @@ impl Board {
 
         if let Some(m) = self.user_move {
             if m.is_empty_prom() {
-                let transform =
-                    tiny_skia::Transform::from_translate(self.overlay_xywh.0, self.overlay_xywh.1);
+                let (x, y, _, _) = self.get_prom_xywh(&m);
+                let transform = tiny_skia::Transform::from_translate(x, y);
                 self.draw_prom_choice(config.get_active_color(), transform, &mut pixmap);
             }
         }
@@ -322,19 +357,15 @@ impl Board {
             resvg::render(&tree, fit, transform, pixmap.as_mut());
         }
 
-        if let GameState::Mate(mate) = config.get_state() {
+        if config.get_state() != GameState::InPlay {
             let transform =
                 tiny_skia::Transform::from_translate(self.overlay_xywh.0, self.overlay_xywh.1);
             self.draw_text(
-                &format!("Check Mate: {}", mate),
+                &config.get_state().to_string(),
                 32.0,
                 transform,
                 &mut pixmap,
             )
-        } else if config.get_state() == GameState::StaleMate {
-            let transform =
-                tiny_skia::Transform::from_translate(self.overlay_xywh.0, self.overlay_xywh.1);
-            self.draw_text(&format!("It's a Stalemate"), 32.0, transform, &mut pixmap)
         }
 
         frame.copy_from_slice(pixmap.data());
@@ -400,9 +431,10 @@ impl Board {
         let pos = self.mouse_state.get_pos();
         let check_side = self.get_check_side() as usize;
         let off = self.ruler_offset as usize;
-        let x = (pos.0.clamp(off, off + self.side_length as usize - 1) - off) / check_side as usize;
-        let y = 7 - (pos.1.clamp(0, self.side_length as usize - 1) / check_side as usize);
-        (x, y).try_into().unwrap()
+        let col =
+            (pos.0.clamp(off, off + self.side_length as usize - 1) - off) / check_side as usize;
+        let row = pos.1.clamp(0, self.side_length as usize - 1) / check_side as usize;
+        self.orient((col, row)).try_into().unwrap()
     }
 
     fn get_font_src() -> Vec<u8> {
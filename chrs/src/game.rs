@@ -0,0 +1,103 @@
+use crate::worker::AISettings;
+use chrs_lib::data::Color;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PlayerKind {
+    Human,
+    Engine,
+}
+
+/// Who plays one side, `settings` are used when it is the engine.
+#[derive(Copy, Clone, Debug)]
+pub struct Player {
+    pub kind: PlayerKind,
+    pub settings: AISettings,
+}
+
+impl Player {
+    pub fn human() -> Self {
+        Self {
+            kind: PlayerKind::Human,
+            settings: Default::default(),
+        }
+    }
+
+    pub fn engine() -> Self {
+        Self {
+            kind: PlayerKind::Engine,
+            settings: Default::default(),
+        }
+    }
+
+    pub fn is_human(&self) -> bool {
+        self.kind == PlayerKind::Human
+    }
+}
+
+/// Players of the game and the autoplay controls of engine vs engine games.
+#[derive(Copy, Clone, Debug)]
+pub struct GameSetup {
+    pub white: Player,
+    pub black: Player,
+    /// Turns the board around from its automatic orientation.
+    pub flip_board: bool,
+    /// Engine vs engine games only continue with `step` while paused.
+    pub paused: bool,
+    step: bool,
+}
+
+impl Default for GameSetup {
+    fn default() -> Self {
+        Self {
+            white: Player::human(),
+            black: Player::engine(),
+            flip_board: false,
+            paused: false,
+            step: false,
+        }
+    }
+}
+
+impl GameSetup {
+    pub fn player(&self, color: Color) -> &Player {
+        match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        }
+    }
+
+    pub fn player_mut(&mut self, color: Color) -> &mut Player {
+        match color {
+            Color::White => &mut self.white,
+            Color::Black => &mut self.black,
+        }
+    }
+
+    pub fn has_human(&self) -> bool {
+        self.white.is_human() || self.black.is_human()
+    }
+
+    pub fn is_engine_vs_engine(&self) -> bool {
+        !self.has_human()
+    }
+
+    /// Black is at the bottom when a human plays Black against the engine.
+    pub fn is_board_flipped(&self) -> bool {
+        let human_black = self.black.is_human() && !self.white.is_human();
+        human_black != self.flip_board
+    }
+
+    /// Let a paused engine vs engine game play a single move.
+    pub fn step(&mut self) {
+        self.step = true;
+    }
+
+    /// Whether the engine to move may start thinking, uses up a pending
+    /// step.
+    pub fn engine_may_move(&mut self) -> bool {
+        if !self.paused || !self.is_engine_vs_engine() {
+            return true;
+        }
+        std::mem::take(&mut self.step)
+    }
+}
@@ -2,7 +2,9 @@
 mod app;
 mod board;
 mod cache;
+mod game;
 mod ui;
+mod worker;
 
 use app::App;
 use pixels::Error;
@@ -1,17 +1,69 @@
 use std::cell::RefCell;
 use std::rc::Rc;
+use std::time::Duration;
 
-use chrs_lib::ai::NegaMaxAI;
+use crate::game::{GameSetup, Player, PlayerKind};
+use crate::worker::{AISettings, AIWorker};
+use chrs_lib::ai::{evaluate_traced, NegaMaxAI, PvLine, MAX_PHASE};
 use chrs_lib::data::BoardConfig;
 use chrs_lib::data::Color;
+use chrs_lib::data::FenError;
+use chrs_lib::data::Move;
+use chrs_lib::generator::MoveGenerator;
 use egui::Slider;
 use egui::{Color32, Context};
 
 pub struct Gui {
     fen: String,
+    fen_error: Option<FenError>,
+    chess960_index: u16,
     bit_board: String,
     show_menu: bool,
     show_about: bool,
+    show_setup: bool,
+    show_moves: bool,
+    move_list: MoveListCache,
+    last_ply: usize,
+}
+
+/// SAN of the moves of the game, recomputed only when the game changes.
+#[derive(Default)]
+struct MoveListCache {
+    line: Vec<Move>,
+    hash: u64,
+    // Move number of the first move and whether Black plays it
+    start: (u16, bool),
+    san: Vec<String>,
+}
+
+impl MoveListCache {
+    fn update(&mut self, config: &BoardConfig, gen: &MoveGenerator) {
+        let line: Vec<Move> = config
+            .move_history
+            .moves()
+            .chain(config.move_history.undone())
+            .collect();
+        if line == self.line && config.get_hash() == self.hash {
+            return;
+        }
+
+        let mut position = config.clone();
+        position.goto_ply(0);
+        self.start = (
+            position.get_fullmove_number(),
+            position.get_active_color() == Color::Black,
+        );
+        self.san = line
+            .iter()
+            .map(|m| {
+                let san = m.to_san(&position, gen);
+                position.make_move(*m);
+                san
+            })
+            .collect();
+        self.line = line;
+        self.hash = config.get_hash();
+    }
 }
 
 impl Gui {
@@ -19,14 +71,27 @@ impl Gui {
     pub fn new() -> Self {
         Self {
             fen: "".to_string(),
+            fen_error: None,
+            chess960_index: 518,
             bit_board: "p".to_string(),
             show_menu: true,
             show_about: false,
+            show_setup: false,
+            show_moves: true,
+            move_list: Default::default(),
+            last_ply: 0,
         }
     }
 
     /// Create the UI using egui.
-    pub fn ui(&mut self, ctx: &Context, config: &mut BoardConfig, ai: &mut NegaMaxAI) {
+    pub fn ui(
+        &mut self,
+        ctx: &Context,
+        config: &mut BoardConfig,
+        gen: &MoveGenerator,
+        worker: &mut AIWorker,
+        setup: &mut GameSetup,
+    ) {
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
             ui.visuals_mut().button_frame = false;
 
@@ -35,45 +100,15 @@ impl Gui {
                 ui.separator();
                 ui.toggle_value(&mut self.show_menu, "☰ Menu");
                 ui.separator();
+                ui.toggle_value(&mut self.show_moves, "📜 Moves");
+                ui.separator();
+                ui.toggle_value(&mut self.show_setup, "♟ Game Setup");
+                ui.separator();
                 ui.toggle_value(&mut self.show_about, "ℹ About");
             });
         });
 
-        egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
-            ui.visuals_mut().button_frame = false;
-
-            ui.horizontal(|ui| {
-                if config.move_history.counter > 0 {
-                    ui.label("Recent Moves: ");
-                    let end = config.move_history.counter;
-                    let start = end.saturating_sub(5);
-                    let mut alpha = 0xff;
-                    for i in (start..end).rev() {
-                        let color = ui.style().visuals.text_color();
-                        ui.label(
-                            egui::RichText::new(format!(
-                                "{}",
-                                config.move_history.list[i as usize].unwrap()
-                            ))
-                            .color(
-                                egui::Color32::from_rgba_unmultiplied(
-                                    color.r(),
-                                    color.g(),
-                                    color.b(),
-                                    alpha,
-                                ),
-                            ),
-                        );
-                        alpha = alpha.saturating_sub(50);
-                        if i != start {
-                            ui.separator();
-                        }
-                    }
-                } else {
-                    ui.label("No moves yet");
-                }
-            });
-        });
+        self.move_list_ui(ctx, config, gen, worker, setup);
 
         egui::Window::new("ℹ About")
             .open(&mut self.show_about)
@@ -97,6 +132,35 @@ impl Gui {
                 });
             });
 
+        egui::Window::new("♟ Game Setup")
+            .open(&mut self.show_setup)
+            .show(ctx, |ui| {
+                ui.columns(2, |columns| {
+                    Self::player_ui(&mut columns[0], "White", &mut setup.white);
+                    Self::player_ui(&mut columns[1], "Black", &mut setup.black);
+                });
+
+                if setup.is_engine_vs_engine() {
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        let label = if setup.paused {
+                            "▶ Resume"
+                        } else {
+                            "⏸ Pause"
+                        };
+                        if ui.button(label).clicked() {
+                            setup.paused = !setup.paused;
+                        }
+                        if ui
+                            .add_enabled(setup.paused, egui::Button::new("⏭ Step"))
+                            .clicked()
+                        {
+                            setup.step();
+                        }
+                    });
+                }
+            });
+
         egui::SidePanel::left("left_Panel")
             .frame(egui::Frame::central_panel(&ctx.style()).inner_margin(5.))
             .show_animated(ctx, self.show_menu, |ui| {
@@ -114,13 +178,11 @@ impl Gui {
 
                 ui.horizontal(|ui| {
                     if ui.button("Reset").clicked() {
+                        worker.cancel();
                         config.reset();
                     }
 
-                    if ui.button("Undo").clicked() {
-                        config.undo();
-                        config.undo();
-                    }
+                    ui.toggle_value(&mut setup.flip_board, "⟲ Flip Board");
                 });
 
                 ui.separator();
@@ -134,23 +196,244 @@ impl Gui {
                     {
                         ui.output_mut(|o| o.copied_text = config.get_fen());
                     }
-                    ui.add(egui::TextEdit::multiline(&mut self.fen));
+                    if ui.add(egui::TextEdit::multiline(&mut self.fen)).changed() {
+                        self.fen_error = None;
+                    }
                     if ui.button("Load Fen").clicked() {
-                        config.load_fen(&self.fen);
+                        match BoardConfig::try_from_fen_strict(&self.fen) {
+                            Ok(c) => {
+                                worker.cancel();
+                                *config = c;
+                            }
+                            Err(e) => self.fen_error = Some(e),
+                        }
+                    }
+                    if let Some(e) = &self.fen_error {
+                        ui.colored_label(Color32::RED, format!("Invalid FEN: {}", e));
+                    }
+                });
+                egui::CollapsingHeader::new("Chess960").show(ui, |ui| {
+                    ui.add(Slider::new(&mut self.chess960_index, 0..=959).text("Position"));
+                    if ui.button("New Game").clicked() {
+                        if let Some(c) = BoardConfig::from_chess960_index(self.chess960_index) {
+                            worker.cancel();
+                            *config = c;
+                        }
                     }
                 });
 
                 ui.separator();
 
                 ui.heading("AI");
-                ui.add(Slider::new(&mut ai.depth, 2..=8).text("Search Depth"));
-                ui.add(Slider::new(&mut ai.quiescence_depth, 2..=8).text("Quiescence Depth"));
+                if worker.is_thinking() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Thinking…");
+                    });
+                    if let Some(info) = worker.get_info() {
+                        ui.label(format!("Depth: {}", info.depth));
+                        ui.label(format!("Score: {}", Self::score_text(info.score)));
+                        ui.label(format!("Nodes Searched: {}", info.nodes));
+                        ui.label(format!("Time Taken: {:?}", info.time));
+                        if info.lines.len() > 1 {
+                            Self::lines_ui(ui, &info.lines);
+                        } else {
+                            let pv: Vec<String> = info.pv.iter().map(|m| m.to_uci()).collect();
+                            ui.label(format!("Best Line: {}", pv.join(" ")));
+                        }
+                    }
+                } else {
+                    let stats = worker.get_stats();
+                    ui.label(format!("Nodes Searched: {}", stats.node_count));
+                    ui.label(format!("Max Depth: {}", stats.max_depth));
+                    ui.label(format!("Time Taken: {:?}", stats.time));
+                }
+
+                ui.separator();
+
+                egui::CollapsingHeader::new("Evaluation").show(ui, |ui| {
+                    Self::eval_ui(ui, config, gen);
+                });
+            });
+    }
+
+    fn score_text(score: i32) -> String {
+        match NegaMaxAI::mate_in(score) {
+            Some(n) => format!("Mate in {}", n),
+            None => format!("{:+.2}", score as f32 / 100.0),
+        }
+    }
+
+    // Lines of a multi-pv search ranked best first
+    fn lines_ui(ui: &mut egui::Ui, lines: &[PvLine]) {
+        egui::Grid::new("ai_lines").striped(true).show(ui, |ui| {
+            for (rank, line) in lines.iter().enumerate() {
+                ui.strong(format!("{}.", rank + 1));
+                ui.label(Self::score_text(line.score));
+                let pv: Vec<String> = line.pv.iter().map(|m| m.to_uci()).collect();
+                ui.label(pv.join(" "));
+                ui.end_row();
+            }
+        });
+    }
+
+    fn eval_ui(ui: &mut egui::Ui, config: &BoardConfig, gen: &MoveGenerator) {
+        let trace = evaluate_traced(config, gen);
+        let pawns = |v: i32| format!("{:+.2}", v as f32 / 100.0);
+        egui::Grid::new("eval_trace").striped(true).show(ui, |ui| {
+            ui.strong("Term");
+            ui.strong("White");
+            ui.strong("Black");
+            ui.strong("Total");
+            ui.end_row();
+            for (name, [white, black]) in trace.terms() {
+                ui.label(name);
+                // Values of one side are blended like the total, mg and eg
+                // are in the hover text.
+                for score in [white, black] {
+                    ui.label(pawns(score.taper(trace.phase)))
+                        .on_hover_text(format!("MG {} EG {}", pawns(score.mg), pawns(score.eg)));
+                }
+                ui.label(pawns((white - black).taper(trace.phase)));
+                ui.end_row();
+            }
+        });
+        ui.label(format!("Phase: {}/{}", trace.phase, MAX_PHASE));
+        ui.strong(format!("Evaluation: {} (White side)", pawns(trace.score())));
+    }
+
+    fn move_list_ui(
+        &mut self,
+        ctx: &Context,
+        config: &mut BoardConfig,
+        gen: &MoveGenerator,
+        worker: &mut AIWorker,
+        setup: &GameSetup,
+    ) {
+        let ply = config.move_history.len();
+        let last = ply + config.move_history.undone().count();
+        let mut goto = None;
+
+        // Text fields keep the arrow keys to themselves.
+        if ctx.memory(|m| m.focus().is_none()) {
+            ctx.input(|i| {
+                if i.key_pressed(egui::Key::ArrowLeft) {
+                    goto = Some(ply.saturating_sub(1));
+                } else if i.key_pressed(egui::Key::ArrowRight) {
+                    goto = Some(ply + 1);
+                } else if i.key_pressed(egui::Key::ArrowUp) {
+                    goto = Some(0);
+                } else if i.key_pressed(egui::Key::ArrowDown) {
+                    goto = Some(last);
+                }
+            });
+        }
+
+        egui::SidePanel::right("moves_panel")
+            .frame(egui::Frame::central_panel(&ctx.style()).inner_margin(5.))
+            .show_animated(ctx, self.show_moves, |ui| {
+                ui.heading("Moves");
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(ply > 0, egui::Button::new("⏮")).clicked() {
+                        goto = Some(0);
+                    }
+                    if ui.add_enabled(ply > 0, egui::Button::new("◀")).clicked() {
+                        goto = Some(ply - 1);
+                    }
+                    if ui.add_enabled(ply < last, egui::Button::new("▶")).clicked() {
+                        goto = Some(ply + 1);
+                    }
+                    if ui.add_enabled(ply < last, egui::Button::new("⏭")).clicked() {
+                        goto = Some(last);
+                    }
+                });
+
+                // The engine waits while an earlier position is looked at.
+                if ply < last && !setup.player(config.get_active_color()).is_human() {
+                    if ui.button("Play From Here").clicked() {
+                        config.move_history.clear_undone();
+                    }
+                }
 
                 ui.separator();
 
-                ui.label(format!("Nodes Searched: {}", ai.stats.node_count));
-                ui.label(format!("Max Depth: {}", ai.stats.max_depth));
-                ui.label(format!("Time Taken: {:?}", ai.stats.time));
+                self.move_list.update(config, gen);
+                let scroll_to_current = ply != self.last_ply;
+                self.last_ply = ply;
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false; 2])
+                    .show(ui, |ui| {
+                        if self.move_list.san.is_empty() {
+                            ui.label("No moves yet");
+                            return;
+                        }
+                        egui::Grid::new("move_list").striped(true).show(ui, |ui| {
+                            let (mut number, black_first) = self.move_list.start;
+                            let mut color = Color::White;
+                            if black_first {
+                                color = Color::Black;
+                                ui.label(format!("{}.", number));
+                                ui.label("…");
+                            }
+                            for (i, san) in self.move_list.san.iter().enumerate() {
+                                if color == Color::White {
+                                    ui.label(format!("{}.", number));
+                                }
+                                let text = if i < ply {
+                                    egui::RichText::new(san)
+                                } else {
+                                    egui::RichText::new(san).weak()
+                                };
+                                let response = ui.selectable_label(i + 1 == ply, text);
+                                if response.clicked() {
+                                    goto = Some(i + 1);
+                                }
+                                if i + 1 == ply && scroll_to_current {
+                                    response.scroll_to_me(None);
+                                }
+                                if color == Color::Black {
+                                    ui.end_row();
+                                    number += 1;
+                                    color = Color::White;
+                                } else {
+                                    color = Color::Black;
+                                }
+                            }
+                        });
+                    });
             });
+
+        if let Some(goto) = goto {
+            let goto = goto.min(last);
+            if goto != ply {
+                worker.cancel();
+                config.goto_ply(goto);
+            }
+        }
+    }
+
+    fn player_ui(ui: &mut egui::Ui, side: &str, player: &mut Player) {
+        ui.strong(side);
+        ui.radio_value(&mut player.kind, PlayerKind::Human, "Human");
+        ui.radio_value(&mut player.kind, PlayerKind::Engine, "Engine");
+        if player.kind == PlayerKind::Engine {
+            Self::engine_settings_ui(ui, &mut player.settings);
+        }
+    }
+
+    fn engine_settings_ui(ui: &mut egui::Ui, settings: &mut AISettings) {
+        ui.add(Slider::new(&mut settings.depth, 2..=8).text("Search Depth"));
+        ui.add(Slider::new(&mut settings.quiescence_depth, 2..=8).text("Quiescence Depth"));
+        ui.add(Slider::new(&mut settings.contempt, -100..=100).text("Contempt"));
+        ui.add(Slider::new(&mut settings.multi_pv, 1..=5).text("Lines"));
+        let mut limit_time = settings.movetime.is_some();
+        ui.checkbox(&mut limit_time, "Limit Move Time");
+        if limit_time {
+            let mut secs = settings.movetime.map_or(2.0, |t| t.as_secs_f32());
+            ui.add(Slider::new(&mut secs, 0.1..=10.0).text("Move Time (s)"));
+            settings.movetime = Some(Duration::from_secs_f32(secs));
+        } else {
+            settings.movetime = None;
+        }
     }
 }
@@ -3,8 +3,10 @@
  */
 mod gui;
 
-use chrs_lib::ai::NegaMaxAI;
+use crate::game::GameSetup;
+use crate::worker::AIWorker;
 use chrs_lib::data::BoardConfig;
+use chrs_lib::generator::MoveGenerator;
 use egui::{ClippedPrimitive, Context, TexturesDelta};
 use egui_wgpu::renderer::{Renderer, ScreenDescriptor};
 use gui::Gui;
@@ -82,12 +84,19 @@ impl GuiFramework {
     }
 
     /// Prepare egui.
-    pub fn prepare(&mut self, window: &Window, config: &mut BoardConfig, ai: &mut NegaMaxAI) {
+    pub fn prepare(
+        &mut self,
+        window: &Window,
+        config: &mut BoardConfig,
+        gen: &MoveGenerator,
+        worker: &mut AIWorker,
+        setup: &mut GameSetup,
+    ) {
         // Run the egui frame and create all paint jobs to prepare for rendering.
         let raw_input = self.egui_state.take_egui_input(window);
         let output = self.egui_ctx.run(raw_input, |egui_ctx| {
             // Draw the demo application.
-            self.gui.ui(egui_ctx, config, ai);
+            self.gui.ui(egui_ctx, config, gen, worker, setup);
         });
 
         self.textures.append(output.textures_delta);
@@ -0,0 +1,101 @@
+/* Runs the AI off the UI thread so the board stays responsive while it
+ * thinks. Natively the search runs on a thread, on the web in a Web Worker
+ * (see `src/bin/chrs-worker.rs`).
+ */
+mod protocol;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native;
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+#[cfg(not(target_arch = "wasm32"))]
+use native::Backend;
+#[cfg(target_arch = "wasm32")]
+use web::Backend;
+
+use chrs_lib::ai::{AIStat, SearchInfo};
+use chrs_lib::data::{BoardConfig, Move};
+use chrs_lib::generator::MoveGenerator;
+pub use protocol::{AISettings, SearchRequest, WorkerResponse};
+
+/// Handle to the background AI. Searches are requested with `search` and
+/// their results collected with `poll`, only the latest request is ever
+/// answered.
+pub struct AIWorker {
+    backend: Backend,
+    next_id: u64,
+    pending: Option<u64>,
+    info: Option<SearchInfo>,
+    stats: AIStat,
+}
+
+impl AIWorker {
+    pub fn new() -> Self {
+        Self {
+            backend: Backend::new(),
+            next_id: 0,
+            pending: None,
+            info: None,
+            stats: Default::default(),
+        }
+    }
+
+    /// Start searching `config`, cancelling the running search if any.
+    pub fn search(&mut self, config: &BoardConfig, settings: AISettings) {
+        self.cancel();
+        self.next_id += 1;
+        self.pending = Some(self.next_id);
+        self.backend.send(SearchRequest {
+            id: self.next_id,
+            config: config.clone(),
+            settings,
+        });
+    }
+
+    /// Abandon the running search, its result will never be returned.
+    pub fn cancel(&mut self) {
+        if let Some(id) = self.pending.take() {
+            self.backend.cancel(id);
+        }
+        self.info = None;
+    }
+
+    pub fn is_thinking(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Progress of the running search.
+    pub fn get_info(&self) -> Option<&SearchInfo> {
+        self.info.as_ref()
+    }
+
+    /// Statistics of the last finished search.
+    pub fn get_stats(&self) -> AIStat {
+        self.stats
+    }
+
+    /// Collect the messages of the worker. Returns the best move once the
+    /// running search is done.
+    pub fn poll(&mut self, gen: &MoveGenerator) -> Option<Move> {
+        while let Some(response) = self.backend.try_recv(gen) {
+            if Some(response.id()) != self.pending {
+                continue;
+            }
+            match response {
+                WorkerResponse::Info(_, info) => self.info = Some(info),
+                WorkerResponse::BestMove(_, m, stats) => {
+                    log::info!("AI response {:?}", stats);
+                    self.pending = None;
+                    self.info = None;
+                    self.stats = stats;
+                    if m.is_none() {
+                        log::info!("AI did not generate any move");
+                    }
+                    return m;
+                }
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,86 @@
+use super::{SearchRequest, WorkerResponse};
+use chrs_lib::ai::{NegaMaxAI, AI};
+use chrs_lib::generator::MoveGenerator;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+pub struct Backend {
+    requests: Sender<SearchRequest>,
+    responses: Receiver<WorkerResponse>,
+    stop: Arc<AtomicBool>,
+    // Highest id of a cancelled request
+    cancelled: Arc<AtomicU64>,
+}
+
+impl Backend {
+    pub fn new() -> Self {
+        let (requests, request_rx) = channel::<SearchRequest>();
+        let (response_tx, responses) = channel();
+        let mut ai = NegaMaxAI::default();
+        let stop = ai.stop_handle();
+        let cancelled = Arc::new(AtomicU64::new(0));
+
+        let worker_stop = Arc::clone(&stop);
+        let worker_cancelled = Arc::clone(&cancelled);
+        thread::Builder::new()
+            .name("chrs-ai".to_string())
+            .spawn(move || {
+                let gen = MoveGenerator::default();
+                while let Ok(mut request) = request_rx.recv() {
+                    // Only the latest request is of interest.
+                    while let Ok(newer) = request_rx.try_recv() {
+                        request = newer;
+                    }
+                    let id = request.id;
+                    if id <= worker_cancelled.load(Ordering::Relaxed) {
+                        continue;
+                    }
+                    worker_stop.store(false, Ordering::Relaxed);
+
+                    // A cancel that came in before the flag was cleared is
+                    // picked up after the next iteration.
+                    let tx = response_tx.clone();
+                    let stop = Arc::clone(&worker_stop);
+                    let cancelled = Arc::clone(&worker_cancelled);
+                    ai.set_info_callback(Box::new(move |info| {
+                        if id <= cancelled.load(Ordering::Relaxed) {
+                            stop.store(true, Ordering::Relaxed);
+                        }
+                        let _ = tx.send(WorkerResponse::Info(id, info.clone()));
+                    }));
+                    request.settings.apply(&mut ai);
+
+                    let m = ai.get_best_move(&request.config, &gen);
+                    let response = WorkerResponse::BestMove(id, m, ai.get_stats());
+                    if response_tx.send(response).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to spawn the AI thread");
+
+        Self {
+            requests,
+            responses,
+            stop,
+            cancelled,
+        }
+    }
+
+    pub fn send(&mut self, request: SearchRequest) {
+        self.requests
+            .send(request)
+            .expect("the AI thread has stopped");
+    }
+
+    pub fn cancel(&mut self, id: u64) {
+        self.cancelled.fetch_max(id, Ordering::Relaxed);
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn try_recv(&mut self, _gen: &MoveGenerator) -> Option<WorkerResponse> {
+        self.responses.try_recv().ok()
+    }
+}
@@ -0,0 +1,247 @@
+use chrs_lib::ai::{AIStat, NegaMaxAI, PvLine, SearchInfo, SearchLimits};
+use chrs_lib::data::{BoardConfig, Move};
+use chrs_lib::generator::MoveGenerator;
+use std::time::Duration;
+
+/// AI settings controlled from the GUI, applied to the worker's AI before
+/// every search.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AISettings {
+    pub depth: usize,
+    pub quiescence_depth: usize,
+    pub contempt: i32,
+    pub movetime: Option<Duration>,
+    /// Number of lines to search, see [`NegaMaxAI::multi_pv`].
+    pub multi_pv: usize,
+}
+
+impl Default for AISettings {
+    fn default() -> Self {
+        let ai = NegaMaxAI::default();
+        Self {
+            depth: ai.depth,
+            quiescence_depth: ai.quiescence_depth,
+            contempt: ai.contempt,
+            movetime: ai.limits.movetime,
+            multi_pv: ai.multi_pv,
+        }
+    }
+}
+
+impl AISettings {
+    pub fn apply(&self, ai: &mut NegaMaxAI) {
+        ai.depth = self.depth;
+        ai.quiescence_depth = self.quiescence_depth;
+        ai.contempt = self.contempt;
+        ai.multi_pv = self.multi_pv;
+        ai.limits = SearchLimits {
+            movetime: self.movetime,
+            ..Default::default()
+        };
+    }
+}
+
+/// Asks the worker for the best move in `config`.
+#[derive(Clone, Debug)]
+pub struct SearchRequest {
+    pub id: u64,
+    pub config: BoardConfig,
+    pub settings: AISettings,
+}
+
+/// Sent back by the worker for the request with the given id.
+#[derive(Clone, Debug)]
+pub enum WorkerResponse {
+    /// Progress after every completed iteration.
+    Info(u64, SearchInfo),
+    /// Result of the search, `None` if there is no legal move.
+    BestMove(u64, Option<Move>, AIStat),
+}
+
+// A Web Worker does not share memory with the page, so on the web requests
+// and responses are sent as text. A position is sent as its starting FEN and
+// the moves played since, which keeps the history needed to detect
+// repetitions. Moves are always written the Chess960 way as that can not be
+// mistaken for a normal king move.
+
+impl SearchRequest {
+    pub fn encode(&self) -> String {
+        let mut start = self.config.clone();
+        while !start.move_history.is_empty() {
+            start.undo();
+        }
+        let moves: Vec<String> = self
+            .config
+            .move_history
+            .moves()
+            .map(|m| m.to_uci_chess960())
+            .collect();
+        let movetime = self
+            .settings
+            .movetime
+            .map_or("-".to_string(), |t| t.as_millis().to_string());
+
+        format!(
+            "{}\n{} {} {} {} {}\n{}\n{}\n{}",
+            self.id,
+            self.settings.depth,
+            self.settings.quiescence_depth,
+            self.settings.contempt,
+            movetime,
+            self.settings.multi_pv,
+            self.config.is_chess960() as u8,
+            start.get_fen(),
+            moves.join(" ")
+        )
+    }
+
+    pub fn decode(s: &str, gen: &MoveGenerator) -> Option<Self> {
+        let mut lines = s.lines();
+        let id = lines.next()?.parse().ok()?;
+
+        let mut settings = lines.next()?.split_whitespace();
+        let depth = settings.next()?.parse().ok()?;
+        let quiescence_depth = settings.next()?.parse().ok()?;
+        let contempt = settings.next()?.parse().ok()?;
+        let movetime = match settings.next()? {
+            "-" => None,
+            t => Some(Duration::from_millis(t.parse().ok()?)),
+        };
+        let multi_pv = settings.next()?.parse().ok()?;
+
+        let chess960 = lines.next()? == "1";
+        let mut config = BoardConfig::try_from_fen(lines.next()?).ok()?;
+        config.set_chess960(chess960);
+        for m in lines.next().unwrap_or_default().split_whitespace() {
+            let m = Move::from_uci(m, &config, gen).ok()?;
+            config.apply_move(m);
+        }
+
+        Some(Self {
+            id,
+            config,
+            settings: AISettings {
+                depth,
+                quiescence_depth,
+                contempt,
+                movetime,
+                multi_pv,
+            },
+        })
+    }
+}
+
+impl WorkerResponse {
+    pub fn id(&self) -> u64 {
+        match self {
+            WorkerResponse::Info(id, _) => *id,
+            WorkerResponse::BestMove(id, _, _) => *id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        match self {
+            WorkerResponse::Info(id, info) => {
+                // The lines follow on a row each, score first
+                let lines: Vec<String> = info
+                    .lines
+                    .iter()
+                    .map(|line| {
+                        let pv: Vec<String> = line.pv.iter().map(|m| m.to_uci_chess960()).collect();
+                        format!("{} {}", line.score, pv.join(" "))
+                    })
+                    .collect();
+                format!(
+                    "info {} {} {} {} {}\n{}",
+                    id,
+                    info.depth,
+                    info.nodes,
+                    info.time.as_micros(),
+                    info.hashfull,
+                    lines.join("\n")
+                )
+            }
+            WorkerResponse::BestMove(id, m, stats) => format!(
+                "bestmove {} {} {} {} {} {} {} {} {} {} {} {} {} {}",
+                id,
+                m.map_or("-".to_string(), |m| m.to_uci_chess960()),
+                stats.node_count,
+                stats.time.as_micros(),
+                stats.max_depth,
+                stats.qnode_count,
+                stats.pvs_researches,
+                stats.aspiration_researches,
+                stats.null_move_cutoffs,
+                stats.lmr_researches,
+                stats.reverse_futility_cutoffs,
+                stats.futility_pruned,
+                stats.delta_pruned,
+                stats.see_pruned
+            ),
+        }
+    }
+
+    /// Reads a response to a search of `config`.
+    pub fn decode(s: &str, config: &BoardConfig, gen: &MoveGenerator) -> Option<Self> {
+        let mut rows = s.lines();
+        let mut tokens = rows.next()?.split_whitespace();
+        let kind = tokens.next()?;
+        let id = tokens.next()?.parse().ok()?;
+        match kind {
+            "info" => {
+                let depth = tokens.next()?.parse().ok()?;
+                let nodes = tokens.next()?.parse().ok()?;
+                let time = Duration::from_micros(tokens.next()?.parse().ok()?);
+                let hashfull = tokens.next()?.parse().ok()?;
+                let mut lines = Vec::new();
+                for row in rows {
+                    let mut tokens = row.split_whitespace();
+                    let score = tokens.next()?.parse().ok()?;
+                    let mut config = config.clone();
+                    let mut pv = Vec::new();
+                    for m in tokens {
+                        let m = Move::from_uci(m, &config, gen).ok()?;
+                        config.make_move(m);
+                        pv.push(m);
+                    }
+                    lines.push(PvLine { score, pv });
+                }
+                let first = lines.first()?.clone();
+                Some(WorkerResponse::Info(
+                    id,
+                    SearchInfo {
+                        depth,
+                        score: first.score,
+                        nodes,
+                        time,
+                        pv: first.pv,
+                        lines,
+                        hashfull,
+                    },
+                ))
+            }
+            "bestmove" => {
+                let m = match tokens.next()? {
+                    "-" => None,
+                    m => Some(Move::from_uci(m, config, gen).ok()?),
+                };
+                let stats = AIStat {
+                    node_count: tokens.next()?.parse().ok()?,
+                    time: Duration::from_micros(tokens.next()?.parse().ok()?),
+                    max_depth: tokens.next()?.parse().ok()?,
+                    qnode_count: tokens.next()?.parse().ok()?,
+                    pvs_researches: tokens.next()?.parse().ok()?,
+                    aspiration_researches: tokens.next()?.parse().ok()?,
+                    null_move_cutoffs: tokens.next()?.parse().ok()?,
+                    lmr_researches: tokens.next()?.parse().ok()?,
+                    reverse_futility_cutoffs: tokens.next()?.parse().ok()?,
+                    futility_pruned: tokens.next()?.parse().ok()?,
+                    delta_pruned: tokens.next()?.parse().ok()?,
+                    see_pruned: tokens.next()?.parse().ok()?,
+                };
+                Some(WorkerResponse::BestMove(id, m, stats))
+            }
+            _ => None,
+        }
+    }
+}
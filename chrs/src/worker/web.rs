@@ -0,0 +1,98 @@
+use super::{SearchRequest, WorkerResponse};
+use chrs_lib::data::BoardConfig;
+use chrs_lib::generator::MoveGenerator;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{MessageEvent, Worker};
+
+// Written by trunk next to the app, see index.html.
+const WORKER_SCRIPT: &str = "./chrs-worker_loader.js";
+
+pub struct Backend {
+    worker: Worker,
+    // Keeps the message handler of `worker` alive.
+    _on_message: Closure<dyn FnMut(MessageEvent)>,
+    messages: Receiver<String>,
+    // Messages sent before the worker has loaded are lost, so the request
+    // waits here until it reports to be ready.
+    ready: bool,
+    queued: Option<String>,
+    // Position of the last request, responses are read against it.
+    position: Option<BoardConfig>,
+}
+
+impl Backend {
+    pub fn new() -> Self {
+        let (sender, messages) = channel();
+        let (worker, on_message) = Self::spawn(sender);
+        Self {
+            worker,
+            _on_message: on_message,
+            messages,
+            ready: false,
+            queued: None,
+            position: None,
+        }
+    }
+
+    fn spawn(sender: Sender<String>) -> (Worker, Closure<dyn FnMut(MessageEvent)>) {
+        let worker = Worker::new(WORKER_SCRIPT).expect("failed to start the AI worker");
+        let on_message = Closure::wrap(Box::new(move |e: MessageEvent| {
+            if let Some(msg) = e.data().as_string() {
+                let _ = sender.send(msg);
+            }
+        }) as Box<dyn FnMut(MessageEvent)>);
+        worker.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        (worker, on_message)
+    }
+
+    pub fn send(&mut self, request: SearchRequest) {
+        let msg = request.encode();
+        self.position = Some(request.config);
+        if self.ready {
+            self.post(&msg);
+        } else {
+            self.queued = Some(msg);
+        }
+    }
+
+    fn post(&self, msg: &str) {
+        if let Err(e) = self.worker.post_message(&JsValue::from_str(msg)) {
+            log::error!("failed to message the AI worker: {:?}", e);
+        }
+    }
+
+    /// A worker can not be interrupted, the search is stopped by replacing
+    /// it with a new one.
+    pub fn cancel(&mut self, _id: u64) {
+        self.worker.terminate();
+        let (sender, messages) = channel();
+        let (worker, on_message) = Self::spawn(sender);
+        self.worker = worker;
+        self._on_message = on_message;
+        self.messages = messages;
+        self.ready = false;
+        self.queued = None;
+        self.position = None;
+    }
+
+    pub fn try_recv(&mut self, gen: &MoveGenerator) -> Option<WorkerResponse> {
+        while let Ok(msg) = self.messages.try_recv() {
+            if msg == "ready" {
+                self.ready = true;
+                if let Some(msg) = self.queued.take() {
+                    self.post(&msg);
+                }
+                continue;
+            }
+            if let Some(config) = &self.position {
+                match WorkerResponse::decode(&msg, config, gen) {
+                    Some(response) => return Some(response),
+                    None => log::error!("unexpected message from the AI worker: {}", msg),
+                }
+            }
+        }
+        None
+    }
+}